@@ -0,0 +1,153 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+use rspotify::client::Spotify;
+use rspotify::model::album::SimplifiedAlbum;
+use rspotify::model::search::SearchResult;
+use rspotify::senum::SearchType;
+
+use crate::cli::Opts;
+use crate::matcher::similarity;
+use crate::provider::TrackDescriptor;
+use crate::retry::RetryPolicy;
+use crate::tidal::{get_tidal_albums_from_files, AlbumDetails};
+
+/// The score an album found by title and artist needs to be accepted
+const ALBUM_THRESHOLD: f64 = 0.7;
+
+/// Where imported albums end up
+#[derive(Debug)]
+pub enum AlbumTarget {
+    Library,
+    Playlist,
+}
+impl FromStr for AlbumTarget {
+    type Err = Error;
+    fn from_str(target: &str) -> Result<Self, Error> {
+        match target {
+            "library" => Ok(AlbumTarget::Library),
+            "playlist" => Ok(AlbumTarget::Playlist),
+            _ => Err(anyhow!("Could not parse an album target, expected library or playlist")),
+        }
+    }
+}
+
+/// Finds every favourite album on spotify and saves it to the user's library
+pub async fn import_albums_to_library(spotify: &Spotify, retry: &RetryPolicy, opts: &Opts) -> Result<(), Error> {
+    let found = find_albums(spotify, retry, opts).await?;
+    let ids: Vec<String> = found.iter()
+        .filter_map(|(_, album)| album.as_ref().and_then(|album| album.id.clone()))
+        .collect();
+
+    if opts.dry_run {
+        println!("> Dry run, would save {} albums..", ids.len());
+        found.iter()
+            .filter_map(|(details, album)| album.as_ref().map(|album| (details, album)))
+            .for_each(|(details, album)| println!("{} - {} -> {}", details.artist.name, details.title, album.uri.as_deref().unwrap_or_default()));
+    } else {
+        println!("> Saving {} albums..", ids.len());
+        for chunk in ids.chunks(20) {
+            match retry.run("Saving albums", || spotify.current_user_saved_albums_add(chunk)).await {
+                Ok(_) => println!("Saved {} albums", chunk.len()),
+                Err(err) => println!("Failed to save because {}", err),
+            }
+        }
+    }
+
+    found.iter()
+        .filter(|(_, album)| album.is_none())
+        .for_each(|(details, _)| println!("Could not find album {} - {}", details.artist.name, details.title));
+    Ok(())
+}
+
+/// Finds every favourite album on spotify and lists all of their tracks, in album order
+pub async fn album_tracks(spotify: &Spotify, retry: &RetryPolicy, opts: &Opts) -> Result<Vec<TrackDescriptor>, Error> {
+    let found = find_albums(spotify, retry, opts).await?;
+    let mut tracks = vec![];
+    for (details, album) in found {
+        let id = match album.and_then(|album| album.id) {
+            Some(id) => id,
+            None => {
+                println!("Could not find album {} - {}", details.artist.name, details.title);
+                continue;
+            }
+        };
+
+        let mut offset = 0;
+        loop {
+            let page = retry.run("Reading album tracks", || spotify.album_track(&id, 50, offset)).await?;
+            for track in &page.items {
+                tracks.push(TrackDescriptor {
+                    title: track.name.clone(),
+                    artist: track.artists.first().map(|artist| artist.name.clone()).unwrap_or_default(),
+                    artists: track.artists.iter().map(|artist| artist.name.clone()).collect(),
+                    album: Some(details.title.clone()),
+                    duration: Some(track.duration_ms / 1000),
+                    explicit: Some(track.explicit),
+                    source_id: Some(details.id.to_string()),
+                    position: tracks.len(),
+                    spotify_uri: Some(track.uri.clone()),
+                    ..Default::default()
+                });
+            }
+            if page.next.is_none() {
+                break;
+            }
+            offset += page.limit;
+        }
+    }
+    println!("> Importing {} album tracks..", tracks.len());
+    Ok(tracks)
+}
+
+async fn find_albums(spotify: &Spotify, retry: &RetryPolicy, opts: &Opts) -> Result<Vec<(AlbumDetails, Option<SimplifiedAlbum>)>, Error> {
+    println!("> Reading tidal albums file..");
+    let albums = get_tidal_albums_from_files(&opts.tidal_file).await?;
+    println!("> Searching {} albums..", albums.items.len());
+
+    let mut found = vec![];
+    for favourite in albums.items {
+        let album = find_album(spotify, retry, &favourite.item).await;
+        found.push((favourite.item, album));
+    }
+    Ok(found)
+}
+
+/// Looks the album up by upc first, then by title and artist
+async fn find_album(spotify: &Spotify, retry: &RetryPolicy, details: &AlbumDetails) -> Option<SimplifiedAlbum> {
+    if let Some(upc) = details.upc.as_ref().filter(|upc| !upc.is_empty()) {
+        let query = format!("upc:{}", upc);
+        let mut albums = search_albums(spotify, retry, &query).await;
+        if !albums.is_empty() {
+            return Some(albums.remove(0));
+        }
+        log::debug!("Nothing found for {}, falling back to title and artist", query);
+    }
+
+    let query = format!("album:{} artist:{}", details.title, details.artist.name);
+    search_albums(spotify, retry, &query).await
+        .into_iter()
+        .map(|album| (album_score(details, &album), album))
+        .filter(|(score, _)| *score >= ALBUM_THRESHOLD)
+        .max_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(_, album)| album)
+}
+
+async fn search_albums(spotify: &Spotify, retry: &RetryPolicy, query: &str) -> Vec<SimplifiedAlbum> {
+    match retry.run("Searching albums", || spotify.search(query, SearchType::Album, 10, 0, None, None)).await {
+        Ok(SearchResult::Albums(albums)) => albums.items,
+        Ok(_) => vec![],
+        Err(err) => {
+            log::debug!("Album search {} failed because {}", query, err);
+            vec![]
+        }
+    }
+}
+
+fn album_score(details: &AlbumDetails, album: &SimplifiedAlbum) -> f64 {
+    let title = similarity(&details.title, &album.name);
+    let artist = album.artists.iter()
+        .map(|artist| similarity(&details.artist.name, &artist.name))
+        .fold(0.0, f64::max);
+    title * 0.6 + artist * 0.4
+}
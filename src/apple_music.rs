@@ -0,0 +1,251 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Error};
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::cli::Opts;
+use crate::provider::{StreamingProvider, TrackDescriptor};
+
+/// The parts of an apple music or itunes Library.xml export we read
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct Library {
+    /// Every track keyed by its track id
+    #[serde(rename = "Tracks", default)]
+    pub tracks: HashMap<String, AppleTrack>,
+    #[serde(rename = "Playlists", default)]
+    pub playlists: Vec<ApplePlaylist>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AppleTrack {
+    #[serde(rename = "Track ID")]
+    pub track_id: i64,
+    pub name: Option<String>,
+    pub artist: Option<String>,
+    #[serde(rename = "Album Artist")]
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    /// Length in milliseconds
+    #[serde(rename = "Total Time")]
+    pub total_time: Option<u64>,
+    pub year: Option<u32>,
+    pub explicit: Option<bool>,
+    pub podcast: Option<bool>,
+    pub movie: Option<bool>,
+    #[serde(rename = "TV Show")]
+    pub tv_show: Option<bool>,
+}
+
+impl AppleTrack {
+    /// Podcasts, movies and tv shows share the library with the music
+    fn is_music(&self) -> bool {
+        !(self.podcast.unwrap_or_default() || self.movie.unwrap_or_default() || self.tv_show.unwrap_or_default())
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ApplePlaylist {
+    pub name: String,
+    /// The library itself shows up as the master playlist
+    #[serde(default)]
+    pub master: bool,
+    /// Folders list the tracks of every playlist inside them
+    #[serde(default)]
+    pub folder: bool,
+    /// Set on the built in lists like Music, Movies or Podcasts
+    #[serde(rename = "Distinguished Kind")]
+    pub distinguished_kind: Option<i64>,
+    #[serde(rename = "Playlist Items", default)]
+    pub playlist_items: Vec<PlaylistItem>,
+}
+
+impl ApplePlaylist {
+    /// Playlists the user made, rather than the library, a folder or one of the built in lists
+    fn is_user_playlist(&self) -> bool {
+        !self.master && !self.folder && self.distinguished_kind.is_none()
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+pub struct PlaylistItem {
+    #[serde(rename = "Track ID")]
+    pub track_id: i64,
+}
+
+/// Reads the whole library, or only the named playlists, out of a Library.xml export
+#[derive(Default, Debug, Clone)]
+pub struct AppleMusicProvider {
+    pub library: PathBuf,
+    pub playlists: Vec<String>,
+}
+
+impl AppleMusicProvider {
+    pub fn new(opts: &Opts) -> AppleMusicProvider {
+        AppleMusicProvider {
+            library: opts.apple_library.as_ref().expect("Failed to unwrap the apple_library parameter").to_path_buf(),
+            playlists: opts.apple_playlist.clone(),
+        }
+    }
+
+    /// The tracks of each named playlist on their own, so each can go to a spotify playlist of the same
+    /// name. Without names every playlist the user made is imported
+    pub async fn playlist_queries(&self) -> Result<Vec<(String, Vec<TrackDescriptor>)>, Error> {
+        let library = self.gather_data().await?;
+        let playlists = if self.playlists.is_empty() {
+            library.playlists.iter().filter(|playlist| playlist.is_user_playlist()).collect()
+        } else {
+            select_playlists(&library, &self.playlists)?
+        };
+        Ok(playlists.iter()
+            .map(|playlist| (playlist.name.clone(), convert_playlist(&library, playlist)))
+            .collect())
+    }
+}
+
+#[async_trait]
+impl StreamingProvider<Library> for AppleMusicProvider {
+    async fn gather_data(&self) -> Result<Library, Error> {
+        println!("> Reading apple music library..");
+        let library: Library = plist::from_file(&self.library)
+            .map_err(|err| anyhow!("Some issue with {} {}", self.library.display(), err))?;
+        println!("> Found {} tracks and {} playlists..", library.tracks.len(), library.playlists.len());
+        Ok(library)
+    }
+
+    fn convert_to_query(&self, item: Library) -> Vec<TrackDescriptor> {
+        if self.playlists.is_empty() {
+            return convert_library(&item);
+        }
+        // build_queries checks the names first, unknown ones are only skipped here
+        let mut tracks: Vec<TrackDescriptor> = self.playlists.iter()
+            .filter_map(|name| item.playlists.iter().find(|playlist| &playlist.name == name))
+            .flat_map(|playlist| convert_playlist(&item, playlist))
+            .collect();
+        tracks.iter_mut().enumerate().for_each(|(position, track)| track.position = position);
+        tracks
+    }
+
+    async fn build_queries(&self) -> Result<Vec<TrackDescriptor>, Error> {
+        let library = self.gather_data().await?;
+        select_playlists(&library, &self.playlists)?;
+        let queries = self.convert_to_query(library);
+        println!("> Importing {} tracks..", queries.len());
+        Ok(queries)
+    }
+}
+
+/// The playlists with the given names in the given order, failing on names the library doesn't have
+fn select_playlists<'a>(library: &'a Library, names: &[String]) -> Result<Vec<&'a ApplePlaylist>, Error> {
+    names.iter()
+        .map(|name| library.playlists.iter()
+            .find(|playlist| &playlist.name == name)
+            .ok_or_else(|| anyhow!("The apple music library has no playlist called {}", name)))
+        .collect()
+}
+
+/// Every music track, in the order of the master playlist when there is one and by track id otherwise
+fn convert_library(library: &Library) -> Vec<TrackDescriptor> {
+    let mut ids: Vec<i64> = match library.playlists.iter().find(|playlist| playlist.master) {
+        Some(master) => master.playlist_items.iter().map(|item| item.track_id).collect(),
+        None => {
+            let mut ids: Vec<i64> = library.tracks.values().map(|track| track.track_id).collect();
+            ids.sort_unstable();
+            ids
+        }
+    };
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(*id));
+    convert_tracks(library, &ids)
+}
+
+fn convert_playlist(library: &Library, playlist: &ApplePlaylist) -> Vec<TrackDescriptor> {
+    let ids: Vec<i64> = playlist.playlist_items.iter().map(|item| item.track_id).collect();
+    convert_tracks(library, &ids)
+}
+
+/// Resolves the track ids against the library, ids it doesn't know are skipped
+fn convert_tracks(library: &Library, ids: &[i64]) -> Vec<TrackDescriptor> {
+    ids.iter()
+        .filter_map(|id| library.tracks.get(&id.to_string()))
+        .filter(|track| track.is_music())
+        .enumerate()
+        .map(|(position, track)| {
+            let artist = track.artist.clone().or_else(|| track.album_artist.clone()).unwrap_or_default();
+            TrackDescriptor {
+                title: track.name.clone().unwrap_or_default(),
+                artists: if artist.is_empty() { vec![] } else { vec![artist.clone()] },
+                artist,
+                album: track.album.clone(),
+                duration: track.total_time.map(|millis| (millis / 1000) as u32),
+                explicit: track.explicit,
+                source_id: Some(track.track_id.to_string()),
+                year: track.year,
+                position,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider(playlists: &[&str]) -> AppleMusicProvider {
+        AppleMusicProvider {
+            library: PathBuf::from("example-library.xml"),
+            playlists: playlists.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    fn titles(tracks: &[TrackDescriptor]) -> Vec<&str> {
+        tracks.iter().map(|track| track.title.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn reads_the_library_in_master_order() {
+        let tracks = provider(&[]).build_queries().await.unwrap();
+
+        assert_eq!(titles(&tracks), vec!["Drown", "Agony", "Killed by Death"]);
+        assert_eq!(tracks[1], TrackDescriptor {
+            title: "Agony".to_string(),
+            artist: "Slaughter to Prevail".to_string(),
+            artists: vec!["Slaughter to Prevail".to_string()],
+            album: Some("Kostolom".to_string()),
+            duration: Some(210),
+            explicit: Some(true),
+            source_id: Some("1".to_string()),
+            position: 1,
+            year: Some(2021),
+            ..Default::default()
+        });
+        assert_eq!(tracks[2].artist, "Motörhead");
+    }
+
+    #[tokio::test]
+    async fn reads_named_playlists() {
+        let tracks = provider(&["Road Trip", "Deathcore"]).build_queries().await.unwrap();
+        assert_eq!(titles(&tracks), vec!["Killed by Death", "Agony", "Agony", "Drown"]);
+        assert!(tracks.iter().enumerate().all(|(position, track)| track.position == position));
+
+        let err = provider(&["Deathcore", "Nope"]).build_queries().await.unwrap_err();
+        assert_eq!(err.to_string(), "The apple music library has no playlist called Nope");
+        assert!(provider(&["Nope"]).playlist_queries().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn splits_only_user_playlists() {
+        let playlists = provider(&[]).playlist_queries().await.unwrap();
+        let names: Vec<&str> = playlists.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Deathcore", "Road Trip", "Empty"]);
+        assert_eq!(titles(&playlists[1].1), vec!["Killed by Death", "Agony"]);
+
+        let playlists = provider(&["Road Trip", "Music"]).playlist_queries().await.unwrap();
+        let names: Vec<&str> = playlists.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, vec!["Road Trip", "Music"]);
+    }
+}
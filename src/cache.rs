@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::matcher::normalize;
+use crate::provider::TrackDescriptor;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedMatch {
+    pub uri: String,
+    pub score: f64,
+    /// Seconds since the unix epoch
+    pub cached_at: u64,
+}
+
+/// Spotify matches from earlier runs, keyed by isrc or normalized query
+#[derive(Debug, Default)]
+pub struct SearchCache {
+    path: PathBuf,
+    ttl: u64,
+    entries: HashMap<String, CachedMatch>,
+}
+
+impl SearchCache {
+    /// Where the cache lives when no other path is given, inside the user's config directory
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("spotify-importer").join("search-cache.json"))
+    }
+
+    /// Reads the cache, starting empty when the file is missing or can't be read
+    pub fn load(path: &Path, ttl_days: u64) -> SearchCache {
+        let entries = match File::open(path) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file)).unwrap_or_else(|err| {
+                println!("> Ignoring unreadable search cache {} because {}", path.display(), err);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        SearchCache {
+            path: path.to_path_buf(),
+            ttl: ttl_days * 24 * 60 * 60,
+            entries,
+        }
+    }
+
+    /// The cached match for the track, unless it has expired
+    pub fn get(&self, track: &TrackDescriptor) -> Option<&CachedMatch> {
+        let now = now();
+        self.entries.get(&cache_key(track))
+            .filter(|cached| now.saturating_sub(cached.cached_at) < self.ttl)
+    }
+
+    pub fn insert(&mut self, track: &TrackDescriptor, uri: String, score: f64) {
+        self.entries.insert(cache_key(track), CachedMatch { uri, score, cached_at: now() });
+    }
+
+    /// Writes the cache back to disk, dropping anything that has expired
+    pub fn save(&mut self) -> Result<(), Error> {
+        let now = now();
+        let ttl = self.ttl;
+        self.entries.retain(|_, cached| now.saturating_sub(cached.cached_at) < ttl);
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = File::create(&self.path)?;
+        serde_json::to_writer(file, &self.entries)?;
+        Ok(())
+    }
+}
+
+/// The isrc when the source has one, the normalized search query otherwise
+pub fn cache_key(track: &TrackDescriptor) -> String {
+    match &track.isrc {
+        Some(isrc) => format!("isrc:{}", isrc.to_uppercase()),
+        None => format!("query:{}", normalize(&track.query())),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default()
+}
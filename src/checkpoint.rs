@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::provider::TrackDescriptor;
+use crate::report::TrackOutcome;
+
+/// How far an import got, each stage builds on the one before it
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Gathered,
+    Searched,
+    Added,
+}
+
+/// Everything needed to pick an import back up without searching or adding tracks twice
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub stage: Stage,
+    pub tracks: Vec<TrackDescriptor>,
+    /// Outcomes of every track searched so far
+    pub outcomes: Vec<TrackOutcome>,
+    /// The playlist id once it is known, so a created playlist is not created again
+    pub playlist: Option<String>,
+    /// How many chunks of tracks have been sent to the playlist
+    pub added_chunks: usize,
+}
+
+impl Checkpoint {
+    pub fn new(tracks: Vec<TrackDescriptor>) -> Checkpoint {
+        Checkpoint {
+            stage: Stage::Gathered,
+            tracks,
+            outcomes: vec![],
+            playlist: None,
+            added_chunks: 0,
+        }
+    }
+
+    /// Where checkpoints are written when no other path is given, inside the user's config directory
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("spotify-importer").join("checkpoint.json"))
+    }
+
+    pub fn load(path: &Path) -> Result<Checkpoint, Error> {
+        let file = File::open(path)
+            .map_err(|err| anyhow!("Failed to open checkpoint {} because {}", path.display(), err))?;
+        let checkpoint = serde_json::from_reader(BufReader::new(file))?;
+        Ok(checkpoint)
+    }
+
+    /// Writes to a temporary file first so a crash mid write leaves the last checkpoint intact
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temporary = path.with_extension("tmp");
+        serde_json::to_writer(File::create(&temporary)?, self)?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    /// Tracks that have not been searched yet
+    pub fn remaining(&self) -> Vec<TrackDescriptor> {
+        let searched: HashSet<usize> = self.outcomes.iter().map(|outcome| outcome.track.position).collect();
+        self.tracks.iter()
+            .filter(|track| !searched.contains(&track.position))
+            .cloned()
+            .collect()
+    }
+}
@@ -1,6 +1,9 @@
 use std::path::PathBuf;
 use structopt::StructOpt;
 use crate::Platform;
+use crate::albums::AlbumTarget;
+use crate::playlist::Visibility;
+use crate::raw::{ColumnMapping, Delimiter};
 
 #[derive(Debug, StructOpt)]
 #[structopt(
@@ -18,26 +21,136 @@ pub struct Opts {
     #[structopt(short = "x", long = "platform", default_value = "tidal")]
     pub platform: Platform,
 
-    /// The playlist to import to
+    /// The playlist to import to, an id, a spotify uri or url, or a name. Names we can't find are created
     #[structopt(short = "p", long = "playlist")]
-    pub playlist: String,
+    pub playlist: Option<String>,
 
-    /// The location of the file to import
-    #[structopt(long = "tidal_file", short = "t", parse(from_os_str), required_if("platform", "tidal"))]
-    pub tidal_file: Option<PathBuf>,
+    /// The visibility of a newly created playlist, public, private or collaborative
+    #[structopt(long = "visibility", default_value = "private")]
+    pub visibility: Visibility,
+
+    /// The description of a newly created playlist, defaults to where and when it was imported from
+    #[structopt(long = "description")]
+    pub description: Option<String>,
+
+    /// The tidal pages to import, several files or a directory of them are merged by offset
+    #[structopt(long = "tidal_file", short = "t", parse(from_os_str), required_ifs(&[("platform", "tidal"), ("platform", "tidal_albums")]))]
+    pub tidal_file: Vec<PathBuf>,
+
+    /// The tidal playlist uuid to fetch from the tidal api, or favourites for your favourite tracks
+    #[structopt(long = "tidal_playlist", required_if("platform", "tidal_api"))]
+    pub tidal_playlist: Option<String>,
+
+    /// The tidal session token used to talk to the tidal api
+    #[structopt(long = "tidal_token", env = "TIDAL_TOKEN", required_if("platform", "tidal_api"))]
+    pub tidal_token: Option<String>,
+
+    /// The base url of the tidal api
+    #[structopt(long = "tidal_api", default_value = "https://api.tidal.com/v1")]
+    pub tidal_api: String,
+
+    /// Where imported tidal albums go, saved to the library or every track added to the playlist
+    #[structopt(long = "albums_to", default_value = "library")]
+    pub albums_to: AlbumTarget,
 
     /// The location of the file to import
     #[structopt(long = "raw_file", short = "r", parse(from_os_str), required_if("platform", "raw"))]
     pub raw_file: Option<PathBuf>,
 
+    /// The character separating raw file columns, tab for tab separated files
+    #[structopt(long = "delimiter", default_value = ",")]
+    pub delimiter: Delimiter,
 
-    /// The playlist to import to
-    #[structopt(short = "y", long = "youtube_playlist", required_if("platform", "youtube"))]
+    /// Reads a raw file field from a header of your choosing, as field=Header, can be given more than once
+    #[structopt(long = "map", number_of_values = 1)]
+    pub map: Vec<ColumnMapping>,
+
+    /// The m3u, m3u8, pls or xspf playlist to import
+    #[structopt(long = "playlist_file", parse(from_os_str), required_ifs(&[("platform", "m3u"), ("platform", "m3u8"), ("platform", "pls"), ("platform", "xspf")]))]
+    pub playlist_file: Option<PathBuf>,
+
+    /// The apple music or itunes Library.xml export to import
+    #[structopt(long = "apple_library", parse(from_os_str), required_ifs(&[("platform", "apple_music"), ("platform", "itunes")]))]
+    pub apple_library: Option<PathBuf>,
+
+    /// An apple music playlist to import instead of the whole library, can be given more than once
+    #[structopt(long = "apple_playlist", number_of_values = 1)]
+    pub apple_playlist: Vec<String>,
+
+    /// Import each --apple_playlist, or every playlist you made when none are given, into its own spotify
+    /// playlist of the same name
+    #[structopt(long = "apple_split")]
+    pub apple_split: bool,
+
+    /// The youtube or youtube music playlist to import, liked for your liked music on youtube music
+    /// which needs --youtube_cookies
+    #[structopt(short = "y", long = "youtube_playlist")]
     pub youtube_playlist: Option<String>,
 
+    /// Saved youtube playlist pages, ytInitialData json or continuation responses to import instead
+    /// of the live playlist, read in the order given. For youtube music these are browse responses
+    #[structopt(long = "youtube_file", parse(from_os_str))]
+    pub youtube_file: Vec<PathBuf>,
+
+    /// A cookie file for youtube, a cookies.txt export or a single cookie header line with the SAPISID
+    /// cookie of a signed in session. Only needed for playlists that are not public and liked music
+    #[structopt(long = "youtube_cookies")]
+    pub youtube_cookies: Option<PathBuf>,
+
+    /// The score between 0 and 1 a spotify track needs to be accepted as a match
+    #[structopt(long = "match_threshold", default_value = "0.6")]
+    pub match_threshold: f64,
+
+    /// How many seconds a spotify track may differ in length from the source track, youtube video lengths are never held to it
+    #[structopt(long = "duration_tolerance", default_value = "10")]
+    pub duration_tolerance: u32,
+
+    /// Where to write a report of every track, csv when the file ends in .csv and json otherwise
+    #[structopt(long = "report", parse(from_os_str))]
+    pub report: Option<PathBuf>,
+
+    /// Search and match as usual but never touch the playlist
+    #[structopt(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Add tracks even when they are already in the playlist or listed twice in the source
+    #[structopt(long = "allow-duplicates")]
+    pub allow_duplicates: bool,
+
+    /// How many times a spotify call is tried before giving up
+    #[structopt(long = "max_attempts", default_value = "5")]
+    pub max_attempts: u32,
+
+    /// How many spotify searches may run at the same time
+    #[structopt(long = "concurrency", default_value = "4")]
+    pub concurrency: usize,
+
+    /// The combined number of spotify requests allowed per second, 0 for no limit
+    #[structopt(long = "requests_per_second", default_value = "10")]
+    pub requests_per_second: u32,
+
+    /// Search spotify for every track, neither reading nor writing the search cache
+    #[structopt(long = "no-cache")]
+    pub no_cache: bool,
+
+    /// Search spotify for every track and overwrite what the search cache holds
+    #[structopt(long = "refresh-cache")]
+    pub refresh_cache: bool,
+
+    /// How many days a cached match stays valid
+    #[structopt(long = "cache_ttl", default_value = "30")]
+    pub cache_ttl: u64,
+
+    /// Where to write the checkpoint of this run, defaults to the config directory
+    #[structopt(long = "checkpoint", parse(from_os_str))]
+    pub checkpoint: Option<PathBuf>,
+
+    /// Pick an interrupted import back up from its checkpoint
+    #[structopt(long = "resume", parse(from_os_str))]
+    pub resume: Option<PathBuf>,
+
 }
 
 pub fn get_opts_args() -> Opts {
-    let opts = Opts::from_args();
-    opts
+    Opts::from_args()
 }
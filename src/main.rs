@@ -1,51 +1,114 @@
 use anyhow::Error;
+use futures::stream::{self, StreamExt};
 use raw::RawProvider;
 use rspotify::client::Spotify;
 use rspotify::model::search::SearchResult;
-use rspotify::model::track::FullTrack;
 use rspotify::model::user::PrivateUser;
 use rspotify::oauth2::{SpotifyClientCredentials, SpotifyOAuth, TokenInfo};
 use rspotify::senum::{SearchType};
 use rspotify::util::get_token;
 use anyhow::anyhow;
 
-use crate::cli::get_opts_args;
-use crate::provider::StreamingProvider;
+use crate::apple_music::AppleMusicProvider;
+use crate::albums::{album_tracks, import_albums_to_library, AlbumTarget};
+use crate::cache::{cache_key, CachedMatch, SearchCache};
+use crate::checkpoint::{Checkpoint, Stage};
+use crate::cli::{get_opts_args, Opts};
+use crate::matcher::{MatchConfig, Matcher};
+use crate::playlist::{create_playlist, find_playlist, get_playlist_track_uris, PlaylistTarget};
+use crate::retry::RetryPolicy;
+use crate::report::{print_summary, write_report, Candidate, MatchStatus, TrackOutcome};
+use crate::provider::{StreamingProvider, TrackDescriptor};
 use crate::tidal::TidalProvider;
+use crate::tidal_api::TidalApiProvider;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::fmt;
 use std::str::FromStr;
+use crate::playlist_file::PlaylistFileProvider;
+use crate::xspf::XspfProvider;
 use crate::youtube::YoutubeProvider;
+use crate::youtube_music::YoutubeMusicProvider;
 
 mod tidal;
+mod tidal_api;
+mod albums;
+mod apple_music;
+mod cache;
+mod checkpoint;
 mod cli;
+mod matcher;
+mod playlist;
+mod playlist_file;
 mod provider;
 mod raw;
+mod rate_limit;
+mod report;
+mod retry;
+mod title;
+mod xspf;
 mod youtube;
+mod youtube_music;
+
+/// How many tracks are searched between checkpoints
+const SEARCH_BATCH_SIZE: usize = 50;
 
 #[derive(Debug)]
 pub enum Platform {
     TIDAL,
+    TIDALALBUMS,
+    TIDALAPI,
     NONE,
     RAW,
     YOUTUBE,
+    YOUTUBEMUSIC,
+    M3U,
+    XSPF,
+    APPLEMUSIC,
 }
 impl FromStr for Platform {
     type Err = Error;
     fn from_str(day: &str) -> Result<Self, Error> {
         match day {
             "tidal" => Ok(Platform::TIDAL),
+            "tidal_albums" => Ok(Platform::TIDALALBUMS),
+            "tidal_api" => Ok(Platform::TIDALAPI),
             "raw" => Ok(Platform::RAW),
             "yt" | "youtube" => Ok(Platform::YOUTUBE),
+            "ytm" | "youtube_music" => Ok(Platform::YOUTUBEMUSIC),
+            "m3u" | "m3u8" | "pls" => Ok(Platform::M3U),
+            "xspf" => Ok(Platform::XSPF),
+            "apple_music" | "itunes" => Ok(Platform::APPLEMUSIC),
             _ => Err(anyhow!("Could not parse a platform")),
         }
     }
 }
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Platform::TIDAL | Platform::TIDALALBUMS | Platform::TIDALAPI => write!(f, "Tidal"),
+            Platform::NONE => write!(f, "nowhere"),
+            Platform::RAW => write!(f, "CSV"),
+            Platform::YOUTUBE => write!(f, "YouTube"),
+            Platform::YOUTUBEMUSIC => write!(f, "YouTube Music"),
+            Platform::M3U => write!(f, "a playlist file"),
+            Platform::XSPF => write!(f, "XSPF"),
+            Platform::APPLEMUSIC => write!(f, "Apple Music"),
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    pretty_env_logger::init();
-
     let opts = get_opts_args();
 
+    // --debug shows the debug logs without having to set RUST_LOG
+    if opts.debug {
+        pretty_env_logger::formatted_builder().filter_level(log::LevelFilter::Debug).init();
+    } else {
+        pretty_env_logger::init();
+    }
+
     // TODO: hone down scope of app before deploying
     let mut oauth = SpotifyOAuth::default()
         .scope("user-read-recently-played playlist-modify-public playlist-modify-private user-follow-read user-follow-modify playlist-modify-private user-library-modify user-library-read")
@@ -53,101 +116,411 @@ async fn main() -> Result<(), Error> {
 
     match get_token(&mut oauth).await {
         Some(token_info) => {
-            let (spotify, user) = get_spotify(token_info).await;
-            let queries = match opts.platform {
-                Platform::TIDAL => {
-                    let provider = TidalProvider::new(&opts);
-                    Ok(provider.build_queries().await?)
-                },
-                Platform::NONE => Err(anyhow::anyhow!("We do not support platform with options {:?}", opts.platform)),
-                Platform::RAW => {
-                    let provider = RawProvider::new(&opts);
-                    Ok(provider.build_queries().await?)
-                },
-                Platform::YOUTUBE => {
-                    let provider = YoutubeProvider::new(&opts);
-                    Ok(provider.build_queries().await?)
+            let retry = RetryPolicy::new(opts.max_attempts, opts.requests_per_second);
+            let (spotify, user) = get_spotify(token_info, &retry).await;
+
+            if let (Platform::TIDALALBUMS, AlbumTarget::Library) = (&opts.platform, &opts.albums_to) {
+                return import_albums_to_library(&spotify, &retry, &opts).await;
+            }
+            if let (Platform::APPLEMUSIC, true) = (&opts.platform, opts.apple_split) {
+                return import_apple_playlists(&spotify, &retry, &user, &opts).await;
+            }
+
+            let checkpoint_path = opts.resume.clone()
+                .or_else(|| opts.checkpoint.clone())
+                .or_else(Checkpoint::default_path);
+            let mut checkpoint = match &opts.resume {
+                Some(path) => {
+                    println!("> Resuming from {}..", path.display());
+                    Checkpoint::load(path)?
+                }
+                None => Checkpoint::new(gather_tracks(&spotify, &retry, &opts).await?),
+            };
+            save_checkpoint(&checkpoint, &checkpoint_path)?;
+
+            import_to_playlist(&spotify, &retry, &user, &opts, &mut checkpoint, &checkpoint_path, opts.playlist.as_deref()).await?;
+
+            print_summary(&checkpoint.outcomes);
+            if let Some(path) = &opts.report {
+                write_report(path, &checkpoint.outcomes)?;
+                println!("> Wrote report to {}", path.display());
+            }
+
+            //TODO dont do this
+            Ok(())
+
+        }
+        None => Err(anyhow::anyhow!("Authentication failed, have you set up your .env file?")),
+    }
+}
+
+/// Searches the checkpoint's tracks and adds the matches to the playlist, picking up wherever the
+/// checkpoint left off
+async fn import_to_playlist(spotify: &Spotify, retry: &RetryPolicy, user: &PrivateUser, opts: &Opts, checkpoint: &mut Checkpoint, checkpoint_path: &Option<PathBuf>, playlist: Option<&str>) -> Result<(), Error> {
+    let target = match &checkpoint.playlist {
+        Some(playlist) => PlaylistTarget::Existing(playlist.clone()),
+        None => {
+            let playlist = playlist
+                .ok_or_else(|| anyhow!("Which playlist should we import to? Pass one with --playlist"))?;
+            find_playlist(spotify, retry, playlist).await?
+        }
+    };
+    if let PlaylistTarget::Existing(playlist) = &target {
+        checkpoint.playlist = Some(playlist.clone());
+    }
+
+    if checkpoint.stage < Stage::Searched {
+        search_tracks(spotify, retry, opts, checkpoint, checkpoint_path).await?;
+
+        checkpoint.outcomes.iter()
+            .filter(|outcome| outcome.status != MatchStatus::Matched)
+            .for_each(|outcome| log::debug!("Could not match {} {} ({:?})", outcome.track.artist, outcome.query, outcome.status));
+
+        if !opts.allow_duplicates {
+            let existing = match &target {
+                PlaylistTarget::Existing(playlist) => {
+                    println!("> Reading existing playlist tracks..");
+                    get_playlist_track_uris(spotify, retry, user, playlist).await?
                 }
-            }?;
-
-            // search for tracks (artist, concat of artists and track title)
-            println!("> Searching tracks..");
-            println!("> Queries {:?}", queries);
-            let mut search_results: Vec<(String, String, Result<SearchResult, _>)> = vec![];
-
-
-            for (artist, query) in queries {
-                let query = sanitize_query(query);
-                let query_cloned = query.clone();
-                let future = spotify.search(
-                    query_cloned.as_str(),
-                    SearchType::Track,
-                    10,
-                    0,
-                    None,
-                    None,
-                );
-                search_results.push((artist, query, future.await));
+                PlaylistTarget::New(_) => HashSet::new(),
+            };
+            mark_duplicates(&mut checkpoint.outcomes, existing);
+        }
+        checkpoint.stage = Stage::Searched;
+        save_checkpoint(checkpoint, checkpoint_path)?;
+    }
+
+    if opts.dry_run {
+        if let PlaylistTarget::New(name) = &target {
+            println!("> Dry run, would create playlist {}..", name);
+        }
+        print_planned_additions(&checkpoint.outcomes);
+    } else if checkpoint.stage < Stage::Added {
+        let playlist = match target {
+            PlaylistTarget::Existing(playlist) => playlist,
+            PlaylistTarget::New(name) => {
+                let playlist = create_playlist(spotify, retry, user, &name, opts).await?;
+                checkpoint.playlist = Some(playlist.clone());
+                save_checkpoint(checkpoint, checkpoint_path)?;
+                playlist
+            }
+        };
+        add_tracks(spotify, retry, user, &playlist, checkpoint, checkpoint_path).await?;
+        checkpoint.stage = Stage::Added;
+        save_checkpoint(checkpoint, checkpoint_path)?;
+    }
+    Ok(())
+}
+
+/// Imports each named apple music playlist, or every one of the user's when none are named, into a
+/// spotify playlist of the same name, every one with its own checkpoint next to the usual one
+async fn import_apple_playlists(spotify: &Spotify, retry: &RetryPolicy, user: &PrivateUser, opts: &Opts) -> Result<(), Error> {
+    let base_path = opts.resume.clone()
+        .or_else(|| opts.checkpoint.clone())
+        .or_else(Checkpoint::default_path);
+
+    let provider = AppleMusicProvider::new(opts);
+    let mut outcomes = vec![];
+    for (name, tracks) in provider.playlist_queries().await? {
+        if tracks.is_empty() {
+            println!("> Skipping empty apple music playlist {}..", name);
+            continue;
+        }
+        println!("> Importing apple music playlist {}..", name);
+        let checkpoint_path = base_path.as_ref().map(|path| playlist_checkpoint_path(path, &name));
+        let mut checkpoint = match &checkpoint_path {
+            Some(path) if opts.resume.is_some() && path.exists() => {
+                println!("> Resuming from {}..", path.display());
+                Checkpoint::load(path)?
+            }
+            _ => Checkpoint::new(tracks),
+        };
+        save_checkpoint(&checkpoint, &checkpoint_path)?;
+
+        import_to_playlist(spotify, retry, user, opts, &mut checkpoint, &checkpoint_path, Some(&name)).await?;
+        print_summary(&checkpoint.outcomes);
+        outcomes.extend(checkpoint.outcomes);
+    }
+
+    if let Some(path) = &opts.report {
+        write_report(path, &outcomes)?;
+        println!("> Wrote report to {}", path.display());
+    }
+    Ok(())
+}
+
+/// checkpoint.json becomes checkpoint-<playlist>.json, keeping only characters safe in file names
+fn playlist_checkpoint_path(path: &Path, playlist: &str) -> PathBuf {
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let slug: String = playlist.chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    path.with_file_name(format!("{}-{}.json", stem, slug))
+}
+
+async fn gather_tracks(spotify: &Spotify, retry: &RetryPolicy, opts: &Opts) -> Result<Vec<TrackDescriptor>, Error> {
+    match opts.platform {
+        Platform::TIDAL => {
+            let provider = TidalProvider::new(opts);
+            Ok(provider.build_queries().await?)
+        },
+        Platform::TIDALALBUMS => album_tracks(spotify, retry, opts).await,
+        Platform::TIDALAPI => {
+            let provider = TidalApiProvider::new(opts);
+            Ok(provider.build_queries().await?)
+        },
+        Platform::NONE => Err(anyhow::anyhow!("We do not support platform with options {:?}", opts.platform)),
+        Platform::RAW => {
+            let provider = RawProvider::new(opts);
+            Ok(provider.build_queries().await?)
+        },
+        Platform::YOUTUBE => {
+            let provider = YoutubeProvider::new(opts);
+            Ok(provider.build_queries().await?)
+        },
+        Platform::YOUTUBEMUSIC => {
+            let provider = YoutubeMusicProvider::new(opts);
+            Ok(provider.build_queries().await?)
+        },
+        Platform::M3U => {
+            let provider = PlaylistFileProvider::new(opts);
+            Ok(provider.build_queries().await?)
+        },
+        Platform::XSPF => {
+            let provider = XspfProvider::new(opts);
+            Ok(provider.build_queries().await?)
+        },
+        Platform::APPLEMUSIC => {
+            let provider = AppleMusicProvider::new(opts);
+            Ok(provider.build_queries().await?)
+        }
+    }
+}
+
+/// Searches every track the checkpoint has no outcome for yet, cached matches skip the search.
+/// The checkpoint is saved after every batch
+async fn search_tracks(spotify: &Spotify, retry: &RetryPolicy, opts: &Opts, checkpoint: &mut Checkpoint, checkpoint_path: &Option<PathBuf>) -> Result<(), Error> {
+    let mut cache = if opts.no_cache {
+        None
+    } else {
+        SearchCache::default_path().map(|path| SearchCache::load(&path, opts.cache_ttl))
+    };
+    let matcher = Matcher::new(MatchConfig {
+        threshold: opts.match_threshold,
+        duration_tolerance: opts.duration_tolerance,
+        ..Default::default()
+    });
+
+    let mut to_search = vec![];
+    let mut from_cache = 0;
+    for track in checkpoint.remaining() {
+        if track.error.is_some() {
+            checkpoint.outcomes.push(invalid_outcome(track));
+            continue;
+        }
+        if track.spotify_uri.is_some() {
+            checkpoint.outcomes.push(known_outcome(track));
+            continue;
+        }
+        let cached = cache.as_ref()
+            .filter(|_| !opts.refresh_cache)
+            .and_then(|cache| cache.get(&track));
+        match cached {
+            Some(cached) => {
+                checkpoint.outcomes.push(cached_outcome(track, cached));
+                from_cache += 1;
             }
+            None => to_search.push(track),
+        }
+    }
 
-            let mut track_uris = vec![];
-            let mut failed_uris = vec![];
-
-            //TODO maybe use par it
-            search_results.iter()
-                .for_each(|(artist, query, find)| {
-                    if let Ok(SearchResult::Tracks(tracks)) = find {
-                        let tracks = tracks.items
-                            .iter()
-                            .filter(|track| {
-                                let artists = build_track_artists(track);
-                                artists.contains(&artist)
-                            }).collect::<Vec<&FullTrack>>();
-                        match tracks.first() {
-                            None => {
-                                let message = format!("Could not find {} {}", artist, query);
-                                failed_uris.push(message);
-                            }
-                            Some(value) => {
-                                let uri = value.uri.clone();
-                                log::debug!("Found {} {:?}", query, uri);
-                                track_uris.push(uri);
-                            }
-                        }
+    // search for tracks (artist, concat of artists and track title)
+    println!("> Searching {} tracks, {} found in cache..", to_search.len(), from_cache);
+    log::debug!("> Queries {:?}", to_search);
+    for batch in to_search.chunks(SEARCH_BATCH_SIZE) {
+        // buffered keeps the source order no matter which search finishes first
+        let search_results: Vec<(TrackDescriptor, String, Result<SearchResult, Error>)> = stream::iter(batch.iter().cloned())
+            .map(|track| async move {
+                let (query, find) = search_track(spotify, retry, &track).await;
+                (track, query, find)
+            })
+            .buffered(opts.concurrency.max(1))
+            .collect()
+            .await;
+
+        //TODO maybe use par it
+        let outcomes: Vec<TrackOutcome> = search_results.into_iter()
+            .map(|(track, query, find)| build_outcome(&matcher, track, query, find))
+            .collect();
+        if let Some(cache) = &mut cache {
+            outcomes.iter()
+                .filter(|outcome| outcome.status == MatchStatus::Matched)
+                .for_each(|outcome| {
+                    if let (Some(uri), Some(score)) = (&outcome.uri, outcome.score) {
+                        cache.insert(&outcome.track, uri.clone(), score);
                     }
                 });
+        }
+        checkpoint.outcomes.extend(outcomes);
+        save_checkpoint(checkpoint, checkpoint_path)?;
+    }
 
-            failed_uris.iter().for_each(|message| log::debug!("{}", message));
+    if let Some(cache) = &mut cache {
+        cache.save()?;
+    }
+    checkpoint.outcomes.sort_by_key(|outcome| outcome.track.position);
+    Ok(())
+}
 
+fn save_checkpoint(checkpoint: &Checkpoint, path: &Option<PathBuf>) -> Result<(), Error> {
+    match path {
+        Some(path) => checkpoint.save(path),
+        None => Ok(()),
+    }
+}
 
-            let mut results = vec![];
-            //TODO at this point we should probably retry
-            let mut futures = track_uris.chunks(80);
-            while let Some(track_ids) = futures.next() {
-                results.push(spotify.user_playlist_add_tracks(
-                    user.id.as_str(),
-                    opts.playlist.as_str(),
-                    &track_ids,
-                    None,
-                ).await);
-            }
+/// Picks the best candidate for a track and keeps the runner ups around for the report, only a
+/// match gets a uri
+fn build_outcome(matcher: &Matcher, track: TrackDescriptor, query: String, find: Result<SearchResult, Error>) -> TrackOutcome {
+    let candidates = match find {
+        Ok(SearchResult::Tracks(tracks)) => tracks.items,
+        Ok(_) => vec![],
+        Err(err) => {
+            log::debug!("Search for {} failed because {}", query, err);
+            vec![]
+        }
+    };
+    let ranked = matcher.rank(&track, &candidates);
+    let best = ranked.first().filter(|best| best.score >= matcher.config.threshold);
+    let status = match best {
+        None => MatchStatus::NotFound,
+        Some(_) if matcher.is_ambiguous(&ranked) => MatchStatus::Ambiguous,
+        Some(_) => MatchStatus::Matched,
+    };
+    if let Some(found) = best {
+        log::debug!("Found {} {:?} with score {:.2}", query, found.track.uri, found.score);
+    }
 
-            results.iter().for_each(|res| {
-                match res {
-                    Ok(result) => println!("Added {:?}", result),
-                    Err(err) => println!("Failed to add because {}", err),
-                }
-            });
+    // an ambiguous track gets no uri, its best guess leads the runner ups instead
+    let (uri, skipped) = match status {
+        MatchStatus::Matched => (best.map(|found| found.track.uri.clone()), 1),
+        _ => (None, 0),
+    };
+    TrackOutcome {
+        uri,
+        score: ranked.first().map(|found| found.score),
+        runner_ups: ranked.iter().skip(skipped).take(3).map(Candidate::from).collect(),
+        cached: false,
+        track,
+        query,
+        status,
+    }
+}
 
-            //TODO dont do this
-            Ok(())
+/// The source already told us the spotify uri, nothing to search for
+fn known_outcome(track: TrackDescriptor) -> TrackOutcome {
+    TrackOutcome {
+        query: String::new(),
+        status: MatchStatus::Matched,
+        uri: track.spotify_uri.clone(),
+        score: Some(1.0),
+        runner_ups: vec![],
+        cached: false,
+        track,
+    }
+}
+
+fn invalid_outcome(track: TrackDescriptor) -> TrackOutcome {
+    TrackOutcome {
+        query: String::new(),
+        status: MatchStatus::Invalid,
+        uri: None,
+        score: None,
+        runner_ups: vec![],
+        cached: false,
+        track,
+    }
+}
+
+fn cached_outcome(track: TrackDescriptor, cached: &CachedMatch) -> TrackOutcome {
+    TrackOutcome {
+        query: cache_key(&track),
+        status: MatchStatus::Matched,
+        uri: Some(cached.uri.clone()),
+        score: Some(cached.score),
+        runner_ups: vec![],
+        cached: true,
+        track,
+    }
+}
 
+/// Leaves out tracks already in the playlist and tracks the source lists more than once
+fn mark_duplicates(outcomes: &mut [TrackOutcome], mut existing: HashSet<String>) {
+    let mut seen = HashSet::new();
+    for outcome in outcomes.iter_mut().filter(|outcome| outcome.status == MatchStatus::Matched) {
+        let uri = match &outcome.uri {
+            Some(uri) => uri.clone(),
+            None => continue,
+        };
+        if existing.remove(&uri) {
+            outcome.status = MatchStatus::AlreadyInPlaylist;
+            seen.insert(uri);
+        } else if !seen.insert(uri) {
+            outcome.status = MatchStatus::Duplicate;
         }
-        None => Err(anyhow::anyhow!("Authentication failed, have you set up your .env file?")),
     }
 }
 
-async fn get_spotify(token_info: TokenInfo) -> (Spotify, PrivateUser) {
+/// Tracks headed for the playlist. Tracks of a chunk that failed to add stay in the list so chunk
+/// positions still line up when an import is resumed
+fn tracks_to_add(outcomes: &[TrackOutcome]) -> Vec<usize> {
+    outcomes.iter()
+        .enumerate()
+        .filter(|(_, outcome)| matches!(outcome.status, MatchStatus::Matched | MatchStatus::AddFailed))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+fn print_planned_additions(outcomes: &[TrackOutcome]) {
+    let matched = tracks_to_add(outcomes);
+    println!("> Dry run, would add {} tracks..", matched.len());
+    matched.iter()
+        .map(|index| &outcomes[*index])
+        .for_each(|outcome| println!("{} - {} -> {} ({:.2})",
+                                     outcome.track.artist,
+                                     outcome.track.title,
+                                     outcome.uri.as_deref().unwrap_or_default(),
+                                     outcome.score.unwrap_or_default()));
+}
+
+/// Adds the matched tracks in chunks of 80, skipping chunks an earlier run already sent. Each chunk
+/// is retried on its own and a chunk that still fails marks all of its tracks
+async fn add_tracks(spotify: &Spotify, retry: &RetryPolicy, user: &PrivateUser, playlist: &str, checkpoint: &mut Checkpoint, checkpoint_path: &Option<PathBuf>) -> Result<(), Error> {
+    let matched = tracks_to_add(&checkpoint.outcomes);
+    for chunk in matched.chunks(80).skip(checkpoint.added_chunks) {
+        let track_ids: Vec<String> = chunk.iter()
+            .filter_map(|index| checkpoint.outcomes[*index].uri.clone())
+            .collect();
+        let result = retry.run_non_idempotent("Adding tracks", || spotify.user_playlist_add_tracks(
+            user.id.as_str(),
+            playlist,
+            &track_ids,
+            None,
+        )).await;
+        match result {
+            Ok(result) => println!("Added {:?}", result),
+            Err(err) => {
+                println!("Failed to add because {}", err);
+                chunk.iter().for_each(|index| checkpoint.outcomes[*index].status = MatchStatus::AddFailed);
+            }
+        }
+        checkpoint.added_chunks += 1;
+        save_checkpoint(checkpoint, checkpoint_path)?;
+    }
+    Ok(())
+}
+
+async fn get_spotify(token_info: TokenInfo, retry: &RetryPolicy) -> (Spotify, PrivateUser) {
     log::debug!("> Getting spotify credentials..");
     let client_credential = SpotifyClientCredentials::default()
         .token_info(token_info)
@@ -158,19 +531,40 @@ async fn get_spotify(token_info: TokenInfo) -> (Spotify, PrivateUser) {
         .build();
 
     println!("> Getting user..");
-    let user = spotify.current_user().await.expect("Failed to get user");
+    let user = retry.run("Getting user", || spotify.current_user()).await.expect("Failed to get user");
 
     (spotify, user)
 }
 
+/// Looks the track up by isrc when the source has one, falling back to a free text search
+/// when the isrc lookup finds nothing
+async fn search_track(spotify: &Spotify, retry: &RetryPolicy, track: &TrackDescriptor) -> (String, Result<SearchResult, Error>) {
+    if let Some(isrc) = &track.isrc {
+        let query = format!("isrc:{}", isrc);
+        let find = retry.run("Searching", || spotify.search(query.as_str(), SearchType::Track, 10, 0, None, None)).await;
+        match find {
+            Ok(SearchResult::Tracks(tracks)) if !tracks.items.is_empty() => {
+                return (query, Ok(SearchResult::Tracks(tracks)));
+            }
+            Ok(_) => log::debug!("Nothing found for {}, falling back to text search", query),
+            Err(err) => log::debug!("Isrc lookup {} failed because {}", query, err),
+        }
+    }
+
+    let query = sanitize_query(track.query());
+    let find = retry.run("Searching", || spotify.search(
+        query.as_str(),
+        SearchType::Track,
+        10,
+        0,
+        None,
+        None,
+    )).await;
+    (query, find)
+}
 
 fn sanitize_query(query: String) -> String {
     let query = query.replace("(feat. ", "");
-    let query = query.replace(")", "");
-    query
+    query.replace(")", "")
 }
 
-
-fn build_track_artists(track: &FullTrack) -> Vec<String> {
-    track.artists.iter().map(|artist| artist.name.to_lowercase()).collect::<Vec<String>>()
-}
\ No newline at end of file
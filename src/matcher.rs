@@ -0,0 +1,350 @@
+use rspotify::model::track::FullTrack;
+use strsim::normalized_levenshtein;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::provider::TrackDescriptor;
+
+/// Words that mark a different recording of the same song
+const VERSION_KEYWORDS: [&str; 9] = ["live", "remix", "acoustic", "instrumental", "demo", "edit", "extended", "karaoke", "cover"];
+
+/// Weights for each part of the score and the score a candidate needs to be accepted
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchConfig {
+    pub threshold: f64,
+    /// Candidates whose title is less similar than this are rejected however well the rest matches
+    pub title_floor: f64,
+    /// How many seconds a candidate may be away from the source length before it is rejected
+    pub duration_tolerance: u32,
+    /// Runner ups scoring this close to the best candidate make a match ambiguous
+    pub ambiguity_margin: f64,
+    pub title_weight: f64,
+    pub artist_weight: f64,
+    pub duration_weight: f64,
+    pub album_weight: f64,
+    pub version_weight: f64,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        MatchConfig {
+            threshold: 0.6,
+            title_floor: 0.5,
+            duration_tolerance: 10,
+            ambiguity_margin: 0.05,
+            title_weight: 0.4,
+            artist_weight: 0.3,
+            duration_weight: 0.15,
+            album_weight: 0.05,
+            version_weight: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Match<'a> {
+    pub track: &'a FullTrack,
+    pub score: f64,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct Matcher {
+    pub config: MatchConfig,
+}
+
+impl Matcher {
+    pub fn new(config: MatchConfig) -> Matcher {
+        Matcher { config }
+    }
+
+    /// Scores every candidate against the source track, best first
+    pub fn rank<'a>(&self, source: &TrackDescriptor, candidates: &'a [FullTrack]) -> Vec<Match<'a>> {
+        let mut ranked: Vec<Match> = candidates.iter()
+            .map(|track| Match { track, score: self.score(source, track) })
+            .collect();
+        ranked.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        ranked
+    }
+
+    /// Whether the top candidates are different songs that scored too close to call
+    pub fn is_ambiguous(&self, ranked: &[Match]) -> bool {
+        match (ranked.first(), ranked.get(1)) {
+            (Some(best), Some(runner_up)) => {
+                runner_up.score >= self.config.threshold
+                    && best.score - runner_up.score < self.config.ambiguity_margin
+                    && !matches_isrc_of(best.track, runner_up.track)
+                    && (normalize(&best.track.name) != normalize(&runner_up.track.name)
+                        || build_track_artists(best.track) != build_track_artists(runner_up.track))
+            }
+            _ => false,
+        }
+    }
+
+    /// A score between 0 and 1, parts the source knows nothing about are left out of the weighting
+    pub fn score(&self, source: &TrackDescriptor, candidate: &FullTrack) -> f64 {
+        if matches_isrc(source, candidate) {
+            return 1.0;
+        }
+        if !self.within_tolerance(source, candidate) {
+            return 0.0;
+        }
+        let title = title_score(&source.title, &candidate.name);
+        if title < self.config.title_floor {
+            return 0.0;
+        }
+
+        let mut parts = vec![
+            (self.config.title_weight, title),
+            (self.config.version_weight, version_score(&source.title, &candidate.name)),
+        ];
+        if let Some(score) = artist_score(source, candidate) {
+            parts.push((self.config.artist_weight, score));
+        }
+        if let Some(duration) = source.duration {
+            parts.push((self.config.duration_weight, duration_score(duration, candidate.duration_ms)));
+        }
+        if let Some(album) = &source.album {
+            parts.push((self.config.album_weight, similarity(album, &candidate.album.name)));
+        }
+
+        let total_weight: f64 = parts.iter().map(|(weight, _)| weight).sum();
+        if total_weight <= 0.0 {
+            return 0.0;
+        }
+        parts.iter().map(|(weight, score)| weight * score).sum::<f64>() / total_weight
+    }
+
+    /// Radio edits, extended mixes and live versions usually differ in length from the track we saved.
+    /// Approximate lengths are too rough to reject on and only lower the score
+    fn within_tolerance(&self, source: &TrackDescriptor, candidate: &FullTrack) -> bool {
+        match source.duration {
+            Some(_) if source.approximate_duration => true,
+            Some(duration) => duration_difference(duration, candidate.duration_ms) <= self.config.duration_tolerance as f64,
+            None => true,
+        }
+    }
+}
+
+fn matches_isrc(source: &TrackDescriptor, candidate: &FullTrack) -> bool {
+    match (&source.isrc, candidate.external_ids.get("isrc")) {
+        (Some(isrc), Some(found_isrc)) => isrc.eq_ignore_ascii_case(found_isrc),
+        _ => false,
+    }
+}
+
+fn matches_isrc_of(track: &FullTrack, other: &FullTrack) -> bool {
+    match (track.external_ids.get("isrc"), other.external_ids.get("isrc")) {
+        (Some(isrc), Some(other_isrc)) => isrc.eq_ignore_ascii_case(other_isrc),
+        _ => false,
+    }
+}
+
+/// Lowercases, strips accents and punctuation and collapses whitespace
+pub fn normalize(text: &str) -> String {
+    let stripped: String = text.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .to_lowercase();
+    stripped.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Similarity of two normalized strings, one containing the other counts as a near match
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize(a), normalize(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+    let contained = if a.contains(&b) || b.contains(&a) { 0.9 } else { 0.0 };
+    normalized_levenshtein(&a, &b).max(contained)
+}
+
+fn title_score(source: &str, candidate: &str) -> f64 {
+    similarity(&strip_brackets(source), &strip_brackets(candidate)).max(similarity(source, candidate))
+}
+
+/// Full marks when both titles agree on which version keywords they carry
+fn version_score(source: &str, candidate: &str) -> f64 {
+    if version_keywords(source) == version_keywords(candidate) { 1.0 } else { 0.0 }
+}
+
+fn version_keywords(title: &str) -> Vec<&'static str> {
+    let normalized = normalize(title);
+    let words: Vec<&str> = normalized.split(' ').collect();
+    VERSION_KEYWORDS.iter()
+        .filter(|keyword| words.contains(keyword))
+        .copied()
+        .collect()
+}
+
+/// The share of source artists that show up on the candidate
+fn artist_score(source: &TrackDescriptor, candidate: &FullTrack) -> Option<f64> {
+    let mut artists = source.artists.clone();
+    if artists.is_empty() && !source.artist.is_empty() {
+        artists.push(source.artist.clone());
+    }
+    if artists.is_empty() {
+        return None;
+    }
+
+    let candidate_artists = build_track_artists(candidate);
+    let found = artists.iter()
+        .filter(|artist| candidate_artists.iter().any(|other| similarity(artist, other) >= 0.9))
+        .count();
+    Some(found as f64 / artists.len() as f64)
+}
+
+/// Full marks within a couple of seconds, nothing once the lengths are half a minute apart
+fn duration_score(source_seconds: u32, candidate_ms: u32) -> f64 {
+    let difference = duration_difference(source_seconds, candidate_ms);
+    if difference <= 2.0 {
+        1.0
+    } else {
+        (1.0 - (difference - 2.0) / 28.0).max(0.0)
+    }
+}
+
+fn duration_difference(source_seconds: u32, candidate_ms: u32) -> f64 {
+    (source_seconds as f64 - candidate_ms as f64 / 1000.0).abs()
+}
+
+/// Drops anything in brackets, e.g. "(feat. someone)" or "[official video]"
+fn strip_brackets(title: &str) -> String {
+    let mut depth = 0;
+    title.chars()
+        .filter(|c| {
+            match c {
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth = (depth - 1).max(0),
+                _ => return depth == 0,
+            }
+            false
+        })
+        .collect()
+}
+
+fn build_track_artists(track: &FullTrack) -> Vec<String> {
+    track.artists.iter().map(|artist| artist.name.to_lowercase()).collect::<Vec<String>>()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn source(title: &str, artist: &str, album: Option<&str>, duration: Option<u32>) -> TrackDescriptor {
+        TrackDescriptor {
+            title: title.to_string(),
+            artist: artist.to_string(),
+            album: album.map(|album| album.to_string()),
+            duration,
+            ..Default::default()
+        }
+    }
+
+    fn candidate(name: &str, artist: &str, album: &str, duration_ms: u32, isrc: Option<&str>) -> FullTrack {
+        let artist = json!({ "external_urls": {}, "href": null, "id": null, "name": artist, "type": "artist", "uri": null });
+        let external_ids = match isrc {
+            Some(isrc) => json!({ "isrc": isrc }),
+            None => json!({}),
+        };
+        serde_json::from_value(json!({
+            "album": {
+                "album_type": "album", "artists": [artist.clone()], "external_urls": {}, "href": null, "id": null,
+                "images": [], "name": album, "type": "album", "uri": null
+            },
+            "artists": [artist],
+            "disc_number": 1,
+            "duration_ms": duration_ms,
+            "explicit": false,
+            "external_ids": external_ids,
+            "external_urls": {},
+            "href": null,
+            "id": null,
+            "is_local": false,
+            "name": name,
+            "popularity": 0,
+            "preview_url": null,
+            "track_number": 1,
+            "type": "track",
+            "uri": format!("spotify:track:{}", normalize(name).replace(' ', "")),
+        })).unwrap()
+    }
+
+    fn agony() -> TrackDescriptor {
+        source("Agony", "Slaughter to Prevail", Some("Kostolom"), Some(210))
+    }
+
+    #[test]
+    fn scores_candidates() {
+        let matcher = Matcher::default();
+
+        let exact = candidate("Agony", "Slaughter to Prevail", "Kostolom", 210_000, None);
+        assert!((matcher.score(&agony(), &exact) - 1.0).abs() < 1e-9);
+
+        let other_song = candidate("Baba Yaga", "Slaughter to Prevail", "Kostolom", 210_000, None);
+        assert_eq!(matcher.score(&agony(), &other_song), 0.0);
+        let other_song = candidate("Demolisher", "Slaughter to Prevail", "Kostolom", 211_000, None);
+        assert_eq!(matcher.score(&agony(), &other_song), 0.0);
+
+        let too_long = candidate("Agony", "Slaughter to Prevail", "Kostolom", 240_000, None);
+        assert_eq!(matcher.score(&agony(), &too_long), 0.0);
+        let mut from_video = agony();
+        from_video.approximate_duration = true;
+        let score = matcher.score(&from_video, &too_long);
+        assert!(score > matcher.config.threshold && score < 1.0, "music video length scored {}", score);
+
+        let mut with_isrc = agony();
+        with_isrc.isrc = Some("gbumv2000001".to_string());
+        let same_recording = candidate("Agony (Remastered)", "Slaughter to Prevail", "Single", 240_000, Some("GBUMV2000001"));
+        assert_eq!(matcher.score(&with_isrc, &same_recording), 1.0);
+
+        let live = candidate("Agony - Live", "Slaughter to Prevail", "Kostolom", 212_000, None);
+        let score = matcher.score(&agony(), &live);
+        assert!(score > matcher.config.threshold && score < 0.9, "live version scored {}", score);
+
+        let unknown_album = source("Agony", "Slaughter to Prevail", None, None);
+        let elsewhere = candidate("Agony", "Slaughter to Prevail", "Compilation", 300_000, None);
+        assert!((matcher.score(&unknown_album, &elsewhere) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ranks_best_first() {
+        let matcher = Matcher::default();
+        let candidates = vec![
+            candidate("Baba Yaga", "Slaughter to Prevail", "Kostolom", 210_000, None),
+            candidate("Agony - Live", "Slaughter to Prevail", "Kostolom", 212_000, None),
+            candidate("Agony", "Slaughter to Prevail", "Kostolom", 210_000, None),
+        ];
+
+        let ranked = matcher.rank(&agony(), &candidates);
+        let names: Vec<&str> = ranked.iter().map(|found| found.track.name.as_str()).collect();
+        assert_eq!(names, vec!["Agony", "Agony - Live", "Baba Yaga"]);
+        assert!(ranked.windows(2).all(|pair| pair[0].score >= pair[1].score));
+    }
+
+    #[test]
+    fn flags_close_calls_between_different_songs() {
+        let matcher = Matcher::default();
+        let best = candidate("Agony", "Slaughter to Prevail", "Kostolom", 210_000, Some("A"));
+        let match_of = |track, score| Match { track, score };
+
+        let other_artist = candidate("Agony", "Someone Else", "Kostolom", 210_000, Some("B"));
+        assert!(matcher.is_ambiguous(&[match_of(&best, 0.9), match_of(&other_artist, 0.88)]));
+        assert!(!matcher.is_ambiguous(&[match_of(&best, 0.9), match_of(&other_artist, 0.8)]));
+        assert!(!matcher.is_ambiguous(&[match_of(&best, 0.62), match_of(&other_artist, 0.58)]));
+
+        let rerelease = candidate("Agony", "Slaughter to Prevail", "Best Of", 210_000, Some("C"));
+        assert!(!matcher.is_ambiguous(&[match_of(&best, 0.9), match_of(&rerelease, 0.9)]));
+
+        let same_isrc = candidate("Agony (Remastered)", "Slaughter to Prevail", "Kostolom", 210_000, Some("a"));
+        assert!(!matcher.is_ambiguous(&[match_of(&best, 0.9), match_of(&same_isrc, 0.9)]));
+
+        assert!(!matcher.is_ambiguous(&[match_of(&best, 0.9)]));
+        assert!(!matcher.is_ambiguous(&[]));
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+use chrono::Local;
+use lazy_static::lazy_static;
+use regex::Regex;
+use rspotify::client::Spotify;
+use rspotify::model::user::PrivateUser;
+
+use crate::cli::Opts;
+use crate::retry::RetryPolicy;
+
+#[derive(Debug)]
+pub enum Visibility {
+    Public,
+    Private,
+    Collaborative,
+}
+impl FromStr for Visibility {
+    type Err = Error;
+    fn from_str(visibility: &str) -> Result<Self, Error> {
+        match visibility {
+            "public" => Ok(Visibility::Public),
+            "private" => Ok(Visibility::Private),
+            "collaborative" => Ok(Visibility::Collaborative),
+            _ => Err(anyhow!("Could not parse a visibility, expected public, private or collaborative")),
+        }
+    }
+}
+
+/// Where the tracks end up, a playlist we already have or one we still need to create
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlaylistTarget {
+    Existing(String),
+    New(String),
+}
+
+/// Resolves the playlist option, which can be an id, a spotify uri or url, or the name of one of the user's playlists.
+/// A name we can't find becomes a new playlist
+pub async fn find_playlist(spotify: &Spotify, retry: &RetryPolicy, playlist: &str) -> Result<PlaylistTarget, Error> {
+    if let Some(id) = parse_playlist_id(playlist)? {
+        return Ok(PlaylistTarget::Existing(id));
+    }
+    if looks_like_id(playlist) {
+        return Ok(PlaylistTarget::Existing(playlist.to_string()));
+    }
+
+    println!("> Looking up playlist {}..", playlist);
+    let mut offset = 0;
+    loop {
+        let page = retry.run("Reading playlists", || spotify.current_user_playlists(50, offset))
+            .await
+            .map_err(|err| anyhow!("Failed to read your playlists because {}", err))?;
+        let found = page.items.iter().find(|item| item.name.eq_ignore_ascii_case(playlist));
+        if let Some(found) = found {
+            return Ok(PlaylistTarget::Existing(found.id.clone()));
+        }
+        if page.next.is_none() {
+            break;
+        }
+        offset += page.limit;
+    }
+    Ok(PlaylistTarget::New(playlist.to_string()))
+}
+
+/// Creates the playlist with the visibility and description from the options, returning its id
+pub async fn create_playlist(spotify: &Spotify, retry: &RetryPolicy, user: &PrivateUser, name: &str, opts: &Opts) -> Result<String, Error> {
+    println!("> Creating playlist {}..", name);
+    let description = opts.description.clone()
+        .unwrap_or_else(|| format!("Imported from {} on {}", opts.platform, Local::now().format("%Y-%m-%d")));
+    let public = matches!(opts.visibility, Visibility::Public);
+    let created = retry.run_non_idempotent("Creating playlist", || spotify.user_playlist_create(user.id.as_str(), name, public, description.clone()))
+        .await
+        .map_err(|err| anyhow!("Failed to create playlist {} because {}", name, err))?;
+
+    if let Visibility::Collaborative = opts.visibility {
+        retry.run("Updating playlist", || spotify.user_playlist_change_detail(user.id.as_str(), &created.id, None, None, None, Some(true)))
+            .await
+            .map_err(|err| anyhow!("Failed to make playlist {} collaborative because {}", name, err))?;
+    }
+    Ok(created.id)
+}
+
+/// Pages through the playlist and collects the uri of every track already in it
+pub async fn get_playlist_track_uris(spotify: &Spotify, retry: &RetryPolicy, user: &PrivateUser, playlist: &str) -> Result<HashSet<String>, Error> {
+    let mut uris = HashSet::new();
+    let mut offset = 0;
+    loop {
+        let page = retry.run("Reading playlist tracks", || spotify.user_playlist_tracks(user.id.as_str(), playlist, None, 100, offset, None))
+            .await
+            .map_err(|err| anyhow!("Failed to read playlist {} because {}", playlist, err))?;
+        uris.extend(page.items.into_iter().filter_map(|item| item.track).map(|track| track.uri));
+        if page.next.is_none() {
+            break;
+        }
+        offset += page.limit;
+    }
+    Ok(uris)
+}
+
+/// Pulls the id out of a spotify:playlist: or spotify:user:<user>:playlist: uri or an open.spotify.com
+/// url. Anything else that points at spotify is an error so it never ends up as a playlist name
+fn parse_playlist_id(playlist: &str) -> Result<Option<String>, Error> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^(?:spotify:(?:user:[^:]+:)?playlist:|https?://open\.spotify\.com/(?:intl-[a-z-]+/)?(?:user/[^/]+/)?playlist/)([A-Za-z0-9]{22})(?:[?#/]|$)").unwrap();
+    }
+    let playlist = playlist.trim();
+    if let Some(id) = RE.captures(playlist).and_then(|captures| captures.get(1)) {
+        return Ok(Some(id.as_str().to_string()));
+    }
+    let lowercase = playlist.to_lowercase();
+    if lowercase.starts_with("spotify:") || lowercase.contains("spotify.com/") || lowercase.contains("spotify.link/") {
+        return Err(anyhow!("Could not read a playlist id from {}, expected a spotify playlist uri or url", playlist));
+    }
+    Ok(None)
+}
+
+fn looks_like_id(playlist: &str) -> bool {
+    playlist.len() == 22 && playlist.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_playlist_ids() {
+        let id = "37i9dQZF1DXcBWIGoYBM5M";
+        let cases = vec![
+            (format!("spotify:playlist:{}", id), Some(id)),
+            (format!("spotify:user:someone:playlist:{}", id), Some(id)),
+            (format!("https://open.spotify.com/playlist/{}", id), Some(id)),
+            (format!("https://open.spotify.com/playlist/{}?si=abc", id), Some(id)),
+            (format!("https://open.spotify.com/intl-de/playlist/{}", id), Some(id)),
+            (format!("https://open.spotify.com/user/someone/playlist/{}", id), Some(id)),
+            ("My Deathcore".to_string(), None),
+            (id.to_string(), None),
+        ];
+
+        for (playlist, expected) in cases {
+            assert_eq!(parse_playlist_id(&playlist).unwrap().as_deref(), expected, "playlist {:?}", playlist);
+        }
+    }
+
+    #[test]
+    fn rejects_unreadable_spotify_links() {
+        let cases = vec![
+            "spotify:album:37i9dQZF1DXcBWIGoYBM5M",
+            "spotify:playlist:short",
+            "https://open.spotify.com/track/37i9dQZF1DXcBWIGoYBM5M",
+            "https://spotify.link/abcdef",
+        ];
+
+        for playlist in cases {
+            assert!(parse_playlist_id(playlist).is_err(), "playlist {:?}", playlist);
+        }
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Error};
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::cli::Opts;
+use crate::provider::{StreamingProvider, TrackDescriptor};
+
+/// One entry of an m3u or pls playlist with whatever metadata the file had for it
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct PlaylistEntry {
+    /// The file path or url the player would open
+    pub path: String,
+    /// The "Artist - Title" display text from #EXTINF or TitleN
+    pub display: Option<String>,
+    /// Length in seconds
+    pub duration: Option<u32>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct PlaylistFile {
+    pub entries: Vec<PlaylistEntry>,
+}
+
+/// Reads local player playlists, m3u and m3u8 with #EXTINF metadata or pls with TitleN and LengthN
+#[derive(Default, Debug, Clone)]
+pub struct PlaylistFileProvider {
+    pub file: PathBuf,
+}
+
+impl PlaylistFileProvider {
+    pub fn new(opts: &Opts) -> PlaylistFileProvider {
+        PlaylistFileProvider {
+            file: opts.playlist_file.as_ref().expect("Failed to unwrap the playlist_file parameter").to_path_buf(),
+        }
+    }
+}
+
+#[async_trait]
+impl StreamingProvider<PlaylistFile> for PlaylistFileProvider {
+    async fn gather_data(&self) -> Result<PlaylistFile, Error> {
+        println!("> Reading playlist file..");
+        let playlist = get_playlist_from_file(&self.file)?;
+        println!("> Importing {} tracks..", playlist.entries.len());
+        Ok(playlist)
+    }
+
+    fn convert_to_query(&self, item: PlaylistFile) -> Vec<TrackDescriptor> {
+        item.entries.into_iter().enumerate().map(|(position, entry)| {
+            // files without metadata still tend to be named "Artist - Title"
+            let (path_artist, path_title) = track_from_path(&entry.path);
+            let (display_artist, title) = match entry.display.as_deref().map(split_artist_title) {
+                Some((artist, title)) if !title.is_empty() => (artist, title),
+                _ => (None, path_title),
+            };
+            let artist = entry.artist.or(display_artist).or(path_artist).unwrap_or_default();
+            TrackDescriptor {
+                title,
+                artists: if artist.is_empty() { vec![] } else { vec![artist.clone()] },
+                artist,
+                album: entry.album,
+                duration: entry.duration,
+                source_id: Some(entry.path),
+                position,
+                ..Default::default()
+            }
+        }).collect()
+    }
+
+    async fn build_queries(&self) -> Result<Vec<TrackDescriptor>, Error> {
+        let playlist = self.gather_data().await?;
+        let queries = self.convert_to_query(playlist);
+        Ok(queries)
+    }
+}
+
+/// Reads the playlist as pls when it says so by extension or [playlist] header, as m3u otherwise
+pub fn get_playlist_from_file(path: &Path) -> Result<PlaylistFile, Error> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read playlist file {}", path.display()))?;
+    let contents = decode_text(bytes);
+    let contents = contents.trim_start_matches('\u{feff}');
+    let is_pls = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("pls"))
+        || contents.trim_start().to_lowercase().starts_with("[playlist]");
+    let entries = if is_pls { parse_pls(contents) } else { parse_m3u(contents) };
+    Ok(PlaylistFile { entries })
+}
+
+fn parse_m3u(contents: &str) -> Vec<PlaylistEntry> {
+    lazy_static! {
+        static ref EXTINF: Regex = Regex::new(r"^#EXTINF:\s*(-?\d+(?:\.\d+)?)[^,]*,(.*)$").unwrap();
+    }
+    let mut entries = vec![];
+    let mut pending = PlaylistEntry::default();
+    for line in contents.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(captures) = EXTINF.captures(line) {
+            pending.duration = parse_length(&captures[1]);
+            pending.display = non_empty(&captures[2]);
+        } else if let Some(artist) = line.strip_prefix("#EXTART:") {
+            pending.artist = non_empty(artist);
+        } else if let Some(album) = line.strip_prefix("#EXTALB:") {
+            pending.album = non_empty(album);
+        } else if !line.starts_with('#') {
+            pending.path = line.to_string();
+            entries.push(pending);
+            pending = PlaylistEntry::default();
+        }
+    }
+    entries
+}
+
+fn parse_pls(contents: &str) -> Vec<PlaylistEntry> {
+    let mut entries: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+    for line in contents.lines().map(str::trim) {
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim().to_lowercase(), value.trim()),
+            None => continue,
+        };
+        let (field, index) = match key.find(|c: char| c.is_ascii_digit()) {
+            Some(start) => key.split_at(start),
+            None => continue,
+        };
+        let index = match index.parse() {
+            Ok(index) => index,
+            Err(_) => continue,
+        };
+        let entry = entries.entry(index).or_default();
+        match field {
+            "file" => entry.path = value.to_string(),
+            "title" => entry.display = non_empty(value),
+            "length" => entry.duration = parse_length(value),
+            _ => {}
+        }
+    }
+    entries.into_values().filter(|entry| !entry.path.is_empty() || entry.display.is_some()).collect()
+}
+
+/// Seconds, players write -1 when they don't know
+fn parse_length(length: &str) -> Option<u32> {
+    length.trim().parse::<f64>().ok().filter(|length| *length > 0.0).map(|length| length.round() as u32)
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    Some(value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+/// Splits "Artist - Title" on the first dash with whitespace around it, text without one is all title
+fn split_artist_title(text: &str) -> (Option<String>, String) {
+    lazy_static! {
+        static ref SEPARATOR: Regex = Regex::new(r"\s+[-–—]\s+").unwrap();
+    }
+    match SEPARATOR.find(text) {
+        Some(found) if !text[..found.start()].trim().is_empty() && !text[found.end()..].trim().is_empty() => {
+            (non_empty(&text[..found.start()]), text[found.end()..].trim().to_string())
+        }
+        _ => (None, text.trim().to_string()),
+    }
+}
+
+/// The artist and title a file path stands for, files are often named "Artist - Title"
+pub fn track_from_path(path: &str) -> (Option<String>, String) {
+    split_artist_title(&title_from_path(path))
+}
+
+/// The file name without extension, leading track number or underscores
+pub fn title_from_path(path: &str) -> String {
+    lazy_static! {
+        static ref TRACK_NUMBER: Regex = Regex::new(r"^\d{1,3}(?:\s*[-._)]\s*|\s+)").unwrap();
+    }
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    let stem = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && extension.len() <= 5 => stem,
+        _ => name,
+    };
+    let stem = percent_decode(stem).replace('_', " ");
+    TRACK_NUMBER.replace(stem.trim(), "").trim().to_string()
+}
+
+/// Decodes %XX escapes in a path or url, bytes that don't make up utf-8 are read as latin-1
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes.get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    decode_text(decoded)
+}
+
+/// Reads the bytes as utf-8, falling back to latin-1 which older players still write
+fn decode_text(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes).unwrap_or_else(|err| err.into_bytes().into_iter().map(char::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, display: Option<&str>, duration: Option<u32>, artist: Option<&str>, album: Option<&str>) -> PlaylistEntry {
+        PlaylistEntry {
+            path: path.to_string(),
+            display: display.map(String::from),
+            duration,
+            artist: artist.map(String::from),
+            album: album.map(String::from),
+        }
+    }
+
+    #[test]
+    fn parses_m3u() {
+        let contents = "#EXTM3U\n\
+            #EXTINF:225,Ben E. King - Stand by Me\n\
+            Music/Ben E. King/Greatest/01 Stand by Me.mp3\n\
+            \n\
+            #EXTINF:-1 tvg-id=\"x\",Killed by Death\n\
+            #EXTART:Motörhead\n\
+            #EXTALB:No Remorse\n\
+            http://example.com/killed.mp3\n\
+            # a comment\n\
+            plain.flac\n";
+
+        assert_eq!(parse_m3u(contents), vec![
+            entry("Music/Ben E. King/Greatest/01 Stand by Me.mp3", Some("Ben E. King - Stand by Me"), Some(225), None, None),
+            entry("http://example.com/killed.mp3", Some("Killed by Death"), None, Some("Motörhead"), Some("No Remorse")),
+            entry("plain.flac", None, None, None, None),
+        ]);
+    }
+
+    #[test]
+    fn parses_pls() {
+        let contents = "[playlist]\n\
+            File2=second.mp3\n\
+            Title2=Motörhead - Killed by Death\n\
+            Length2=-1\n\
+            File1=first.mp3\n\
+            Title1=Stand by Me\n\
+            Length1=180.6\n\
+            Title3=\n\
+            NumberOfEntries=2\n\
+            Version=2\n";
+
+        assert_eq!(parse_pls(contents), vec![
+            entry("first.mp3", Some("Stand by Me"), Some(181), None, None),
+            entry("second.mp3", Some("Motörhead - Killed by Death"), None, None, None),
+        ]);
+    }
+
+    #[test]
+    fn reads_titles_from_paths() {
+        let cases = vec![
+            ("Music/Artist/Album/01 - Stand by Me.mp3", "Stand by Me"),
+            ("C:\\Music\\02_Killed_by_Death.flac", "Killed by Death"),
+            ("file:///home/me/Artist%20-%20Song.m4a", "Artist - Song"),
+            ("1999.mp3", "1999"),
+            ("no extension", "no extension"),
+            ("http://example.com/stream", "stream"),
+            ("file:///music/a%C3%A9.mp3", "aé"),
+            ("Mot%F6rhead.mp3", "Motörhead"),
+            ("100%.mp3", "100%"),
+        ];
+
+        for (path, expected) in cases {
+            assert_eq!(title_from_path(path), expected, "path {:?}", path);
+        }
+    }
+
+    #[test]
+    fn decodes_latin1_files() {
+        assert_eq!(decode_text(b"#EXTINF:10,Mot\xf6rhead - Ace of Spades".to_vec()), "#EXTINF:10,Motörhead - Ace of Spades");
+        assert_eq!(decode_text("Motörhead".as_bytes().to_vec()), "Motörhead");
+    }
+
+    #[test]
+    fn splits_artist_and_title_on_a_dash_only() {
+        let provider = PlaylistFileProvider::default();
+        let entries = vec![
+            entry("Ben E. King/Greatest/01 Stand by Me.mp3", Some("Stand by Me"), None, None, None),
+            entry("killed.mp3", Some("Motörhead - Killed by Death"), None, None, None),
+            entry("/home/me/Downloads/03 Motörhead - Ace of Spades.mp3", None, None, None, None),
+            entry("C:\\Users\\me\\Music\\Bijou.mp3", None, None, None, None),
+            entry("Music/Queen/Innuendo/Innuendo.mp3", None, None, Some("Queen"), None),
+            entry("song by someone.mp3", None, None, None, None),
+        ];
+
+        let tracks: Vec<(String, String)> = provider.convert_to_query(PlaylistFile { entries })
+            .into_iter()
+            .map(|track| (track.artist, track.title))
+            .collect();
+        assert_eq!(tracks, vec![
+            ("".to_string(), "Stand by Me".to_string()),
+            ("Motörhead".to_string(), "Killed by Death".to_string()),
+            ("Motörhead".to_string(), "Ace of Spades".to_string()),
+            ("".to_string(), "Bijou".to_string()),
+            ("Queen".to_string(), "Innuendo".to_string()),
+            ("".to_string(), "song by someone".to_string()),
+        ]);
+    }
+}
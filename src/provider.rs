@@ -1,10 +1,55 @@
-
 use anyhow::Error;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Everything a provider knows about a single source track, filled in as far as the source allows
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackDescriptor {
+    pub title: String,
+    /// The primary artist, empty when the source could not tell us
+    pub artist: String,
+    pub artists: Vec<String>,
+    pub album: Option<String>,
+    /// Track length in seconds
+    pub duration: Option<u32>,
+    /// The length comes from something other than the recording itself, e.g. a music video, so it
+    /// only counts towards the score and never rejects a candidate
+    #[serde(default)]
+    pub approximate_duration: bool,
+    pub isrc: Option<String>,
+    pub explicit: Option<bool>,
+    /// The id of the track on the source platform
+    pub source_id: Option<String>,
+    /// Where the track sits in the source, used to keep the playlist order
+    pub position: usize,
+    /// A spotify uri the source already knows, such tracks skip the search
+    pub spotify_uri: Option<String>,
+    /// The release year, when the source knows it
+    pub year: Option<u32>,
+    /// Why the source could not read this track, such tracks are reported and never searched
+    pub error: Option<String>,
+}
+
+impl TrackDescriptor {
+    /// Builds the free text spotify search query, all artists followed by the title
+    pub fn query(&self) -> String {
+        let mut parts = self.artists.clone();
+        if parts.is_empty() && !self.artist.is_empty() {
+            parts.push(self.artist.clone());
+        }
+        parts.push(self.title.clone());
+        parts.join(" ").to_lowercase()
+    }
+}
+
+/// Seconds in a "225", "3:45" or "1:02:03" duration
+pub fn parse_duration(duration: &str) -> Option<u32> {
+    duration.split(':').try_fold(0, |total: u32, part| part.trim().parse::<u32>().ok().map(|part| total * 60 + part))
+}
 
 #[async_trait]
 pub trait StreamingProvider<T> {
     async fn gather_data(&self) -> Result<T, Error>;
-    fn convert_to_query(&self, item: T) -> Vec<(String, String)>;
-    async fn build_queries(&self) -> Result<Vec<(String, String)>, Error>;
-}
\ No newline at end of file
+    fn convert_to_query(&self, item: T) -> Vec<TrackDescriptor>;
+    async fn build_queries(&self) -> Result<Vec<TrackDescriptor>, Error>;
+}
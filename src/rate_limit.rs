@@ -0,0 +1,40 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tokio::time::delay_for;
+
+/// Spaces requests out evenly so concurrent callers stay under a combined rate
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// A limiter allowing the given number of requests per second, zero means no limit
+    pub fn new(requests_per_second: u32) -> RateLimiter {
+        let interval = if requests_per_second == 0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs(1) / requests_per_second
+        };
+        RateLimiter {
+            interval,
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits until it is our turn to send a request
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut next_slot = self.next_slot.lock().expect("Rate limiter lock was poisoned");
+            let now = Instant::now();
+            let slot = (*next_slot).max(now);
+            *next_slot = slot + self.interval;
+            slot - now
+        };
+        if wait > Duration::from_secs(0) {
+            delay_for(wait).await;
+        }
+    }
+}
@@ -1,41 +1,169 @@
-use std::{fs::File, io, path::PathBuf};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::str::FromStr;
 
-use crate::{cli::Opts, provider::StreamingProvider};
-use anyhow::Error;
+use crate::{cli::Opts, provider::{parse_duration, StreamingProvider, TrackDescriptor}};
+use anyhow::{anyhow, Error};
 use async_trait::async_trait;
-use csv::Reader;
+use csv::{ReaderBuilder, StringRecord};
+use lazy_static::lazy_static;
+use regex::Regex;
 use serde::Deserialize;
 
+/// The columns the raw importer understands and the normalized headers accepted for each
+const COLUMN_ALIASES: [(&str, &[&str]); 8] = [
+    ("artist", &["artist", "artist name", "artists", "artist names", "artist name(s)"]),
+    ("track", &["track", "track name", "title", "song", "song title", "song name", "name"]),
+    ("album", &["album", "album name", "album title"]),
+    ("isrc", &["isrc"]),
+    ("duration", &["duration", "length", "time"]),
+    ("duration_ms", &["duration ms", "duration (ms)"]),
+    ("year", &["year", "release year", "release date", "album release date"]),
+    ("spotify_uri", &["spotify uri", "uri", "track uri", "spotify track uri"]),
+];
+
+/// The byte separating columns, parsed from a single character or tab
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Delimiter(pub u8);
+
+impl FromStr for Delimiter {
+    type Err = Error;
+    fn from_str(delimiter: &str) -> Result<Self, Error> {
+        match delimiter {
+            "tab" | "\\t" | "\t" => Ok(Delimiter(b'\t')),
+            delimiter if delimiter.len() == 1 => Ok(Delimiter(delimiter.as_bytes()[0])),
+            _ => Err(anyhow!("Could not parse a delimiter, expected a single character or tab")),
+        }
+    }
+}
+
+/// Reads the column for a field from a header the aliases don't know, given as field=Header
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMapping {
+    pub field: String,
+    pub header: String,
+}
+
+impl FromStr for ColumnMapping {
+    type Err = Error;
+    fn from_str(mapping: &str) -> Result<Self, Error> {
+        let (field, header) = mapping.split_once('=')
+            .ok_or_else(|| anyhow!("Could not parse a column mapping, expected field=Header"))?;
+        let field = canonical_header(field);
+        if !COLUMN_ALIASES.iter().any(|(column, _)| *column == field) {
+            let columns: Vec<&str> = COLUMN_ALIASES.iter().map(|(column, _)| *column).collect();
+            return Err(anyhow!("Could not map {}, expected one of {}", field, columns.join(", ")));
+        }
+        Ok(ColumnMapping { field, header: header.trim().to_string() })
+    }
+}
 
 pub struct Raw {
-    queries: Vec<RawRecord>
+    queries: Vec<RawRow>
 }
 impl Raw {
-    fn new_from_records(records: Vec<RawRecord>) -> Raw {
+    fn new_from_rows(rows: Vec<RawRow>) -> Raw {
         Raw {
-            queries: records
+            queries: rows
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
+/// A row of the file with the line it came from, rows that could not be read keep the reason
+struct RawRow {
+    line: u64,
+    record: Result<RawRecord, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
 struct RawRecord {
+    #[serde(default)]
     artist: String,
-    track: String
+    #[serde(default)]
+    track: String,
+    album: Option<String>,
+    isrc: Option<String>,
+    duration: Option<String>,
+    duration_ms: Option<String>,
+    year: Option<String>,
+    spotify_uri: Option<String>,
+}
+
+impl RawRecord {
+    /// Builds the descriptor. Only a spotify uri that can't be read leaves an error on it, optional
+    /// values that can't be read are left out with a warning
+    fn into_descriptor(self, line: u64, position: usize) -> TrackDescriptor {
+        let artists = split_artists(&self.artist);
+        let mut descriptor = TrackDescriptor {
+            title: self.track.trim().to_string(),
+            artist: artists.first().cloned().unwrap_or_default(),
+            artists,
+            album: self.album.as_deref().map(str::trim).filter(|album| !album.is_empty()).map(String::from),
+            isrc: self.isrc.as_deref().map(str::trim).filter(|isrc| !isrc.is_empty()).map(str::to_uppercase),
+            duration: warn_unreadable(line, "duration", self.read_duration()),
+            year: warn_unreadable(line, "year", self.read_year()),
+            source_id: Some(line.to_string()),
+            position,
+            ..Default::default()
+        };
+        match self.read_spotify_uri() {
+            Err(err) => descriptor.error = Some(format!("line {}: {}", line, err)),
+            Ok(None) if descriptor.title.is_empty() => {
+                descriptor.error = Some(format!("line {}: the row has neither a track nor a spotify uri", line));
+            }
+            Ok(uri) => descriptor.spotify_uri = uri,
+        }
+        descriptor
+    }
+
+    fn read_spotify_uri(&self) -> Result<Option<String>, String> {
+        non_empty(&self.spotify_uri)
+            .map(|uri| parse_track_uri(uri).ok_or_else(|| format!("{} is not a spotify track uri", uri)))
+            .transpose()
+    }
+
+    /// The duration in seconds, from the duration column and otherwise from the milliseconds
+    fn read_duration(&self) -> Result<Option<u32>, String> {
+        match (non_empty(&self.duration), non_empty(&self.duration_ms)) {
+            (Some(duration), _) => parse_duration(duration).map(Some).ok_or_else(|| format!("{} is not a duration", duration)),
+            (None, Some(millis)) => millis.parse::<u32>().map(|millis| Some(millis / 1000)).map_err(|_| format!("{} is not a duration in milliseconds", millis)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    fn read_year(&self) -> Result<Option<u32>, String> {
+        non_empty(&self.year)
+            .map(|year| year.get(..4).and_then(|year| year.parse().ok()).ok_or_else(|| format!("{} is not a year", year)))
+            .transpose()
+    }
+}
+
+/// The value when it could be read, otherwise warns and leaves it out
+fn warn_unreadable<T>(line: u64, field: &str, value: Result<Option<T>, String>) -> Option<T> {
+    value.unwrap_or_else(|err| {
+        println!("> Warning, line {}: {}, leaving out the {}", line, err, field);
+        None
+    })
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|value| !value.is_empty())
 }
 
 #[derive(Default, Debug, Clone)]
 pub struct RawProvider {
-    pub playlist: String,
     pub file: PathBuf,
+    pub delimiter: u8,
+    pub mappings: Vec<ColumnMapping>,
 }
 
 
 impl RawProvider {
     pub fn new(opts: &Opts) -> RawProvider {
         RawProvider {
-            playlist: opts.playlist.to_owned(),
             file: opts.raw_file.as_ref().expect("Failed to unwrap the raw_file parameter").to_path_buf(),
+            delimiter: opts.delimiter.0,
+            mappings: opts.map.clone(),
         }
     }
 }
@@ -44,32 +172,217 @@ impl RawProvider {
 #[async_trait]
 impl StreamingProvider<Raw> for RawProvider {
 
-    //TODO should be response dto from reading a csv of raws
     async fn gather_data(&self) -> Result<Raw, anyhow::Error> {
         println!("> Reading csv file..");
-        let raw = get_raws_from_file(&self.file)?;
-        // read a source
+        let raw = get_raws_from_file(&self.file, self.delimiter, &self.mappings)?;
         println!("> Importing {} tracks..", raw.queries.len());
         Ok(raw)
     }
 
-    fn convert_to_query(&self, item: Raw) -> Vec<(String, String)> {
-        item.queries.iter().map(|query| (query.artist.to_lowercase(), query.track.to_lowercase())).collect()
+    fn convert_to_query(&self, item: Raw) -> Vec<TrackDescriptor> {
+        item.queries.into_iter()
+            .enumerate()
+            .map(|(position, row)| {
+                let line = row.line;
+                let descriptor = match row.record {
+                    Ok(record) => record.into_descriptor(line, position),
+                    Err(err) => TrackDescriptor {
+                        source_id: Some(line.to_string()),
+                        position,
+                        error: Some(format!("line {}: {}", line, err)),
+                        ..Default::default()
+                    },
+                };
+                if let Some(err) = &descriptor.error {
+                    println!("> Skipping {}", err);
+                }
+                descriptor
+            }).collect()
     }
 
-    async fn build_queries(&self) -> Result<Vec<(String, String)>, anyhow::Error> {
+    async fn build_queries(&self) -> Result<Vec<TrackDescriptor>, anyhow::Error> {
         let provider = self.gather_data().await?;
         let queries = self.convert_to_query(provider);
         Ok(queries)
     }
 }
 
-pub fn get_raws_from_file(path: &PathBuf) -> Result<Raw, Error> {
-    let mut rdr = Reader::from_path(path)?;
-    let mut raw_records: Vec<RawRecord> = vec![];
-    // TODO further rayon ops maybe
-    for result in rdr.deserialize() {
-        raw_records.push(result?);
-    }   
-    Ok(Raw::new_from_records(raw_records))
-}
\ No newline at end of file
+/// Reads every row, rows that don't deserialize are kept with their line number instead of failing the file.
+/// Mapped headers win over the aliases for their field
+pub fn get_raws_from_file(path: &PathBuf, delimiter: u8, mappings: &[ColumnMapping]) -> Result<Raw, Error> {
+    let mut rdr = ReaderBuilder::new().delimiter(delimiter).from_path(path)?;
+    for mapping in mappings {
+        if !rdr.headers()?.iter().any(|header| normalize_header(header) == normalize_header(&mapping.header)) {
+            return Err(anyhow!("{} has no {} column to map to {}", path.display(), mapping.header, mapping.field));
+        }
+    }
+
+    let mapped: HashSet<&str> = mappings.iter().map(|mapping| mapping.field.as_str()).collect();
+    let mut seen = HashSet::new();
+    let headers: StringRecord = rdr.headers()?.iter()
+        .map(|header| {
+            let normalized = normalize_header(header);
+            let ignored = format!("{} (ignored)", normalized);
+            let column = match mappings.iter().find(|mapping| normalize_header(&mapping.header) == normalized) {
+                Some(mapping) => mapping.field.clone(),
+                // a mapped field no longer picks up headers through its aliases
+                None => Some(canonical_header(header)).filter(|column| !mapped.contains(column.as_str())).unwrap_or_else(|| ignored.clone()),
+            };
+            // only the first header for a column counts, later ones are kept apart so the row still reads
+            if seen.insert(column.clone()) { column } else { ignored }
+        })
+        .collect();
+    if !headers.iter().any(|header| header == "track" || header == "spotify_uri") {
+        return Err(anyhow!("{} needs a track or spotify uri column, found {:?}", path.display(), rdr.headers()?));
+    }
+
+    let mut rows = vec![];
+    for result in rdr.records() {
+        let row = match result {
+            Ok(record) => RawRow {
+                line: record.position().map(|position| position.line()).unwrap_or_default(),
+                record: record.deserialize(Some(&headers)).map_err(|err| err.to_string()),
+            },
+            Err(err) => RawRow {
+                line: err.position().map(|position| position.line()).unwrap_or_default(),
+                record: Err(err.to_string()),
+            },
+        };
+        rows.push(row);
+    }
+    Ok(Raw::new_from_rows(rows))
+}
+
+/// The column a header stands for, unknown headers are kept as they are and ignored
+fn canonical_header(header: &str) -> String {
+    let normalized = normalize_header(header);
+    COLUMN_ALIASES.iter()
+        .find(|(_, aliases)| aliases.contains(&normalized.as_str()))
+        .map(|(column, _)| column.to_string())
+        .unwrap_or(normalized)
+}
+
+/// Splits a cell holding several artists on ; , & and feat.
+fn split_artists(cell: &str) -> Vec<String> {
+    lazy_static! {
+        static ref SEPARATOR: Regex = Regex::new(r"(?i)\s*[;,]\s*|\s+&\s+|\s+(?:feat\.?|ft\.?|featuring)\s+").unwrap();
+    }
+    SEPARATOR.split(cell)
+        .map(|artist| artist.trim())
+        .filter(|artist| !artist.is_empty())
+        .map(|artist| artist.to_string())
+        .collect()
+}
+
+fn normalize_header(header: &str) -> String {
+    header.trim().trim_start_matches('\u{feff}').to_lowercase().replace('_', " ")
+        .split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// The spotify:track: uri for a track uri or open.spotify.com url
+pub fn parse_track_uri(uri: &str) -> Option<String> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^(?:spotify:track:|https?://open\.spotify\.com/(?:intl-[a-z-]+/)?track/)([A-Za-z0-9]{22})").unwrap();
+    }
+    RE.captures(uri)
+        .and_then(|captures| captures.get(1))
+        .map(|id| format!("spotify:track:{}", id.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_file(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("raw-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn read(name: &str, contents: &str, delimiter: u8, mappings: &[&str]) -> Result<Vec<TrackDescriptor>, Error> {
+        let path = write_file(name, contents);
+        let mappings: Vec<ColumnMapping> = mappings.iter().map(|mapping| mapping.parse().unwrap()).collect();
+        let raw = get_raws_from_file(&path, delimiter, &mappings);
+        std::fs::remove_file(&path).unwrap();
+        Ok(RawProvider::default().convert_to_query(raw?))
+    }
+
+    #[test]
+    fn reads_alias_headers() {
+        let tracks = read("aliases.csv", "\u{feff}Track Name,Artist Name(s),Album Name,Duration (ms),Release Date,ISRC\n\
+            Agony,Slaughter to Prevail,Kostolom,210500,2021-09-17,gbumv2000001\n", b',', &[]).unwrap();
+
+        assert_eq!(tracks, vec![TrackDescriptor {
+            title: "Agony".to_string(),
+            artist: "Slaughter to Prevail".to_string(),
+            artists: vec!["Slaughter to Prevail".to_string()],
+            album: Some("Kostolom".to_string()),
+            duration: Some(210),
+            isrc: Some("GBUMV2000001".to_string()),
+            source_id: Some("2".to_string()),
+            year: Some(2021),
+            ..Default::default()
+        }]);
+    }
+
+    #[test]
+    fn reads_tab_separated_files_with_duplicate_headers() {
+        let delimiter: Delimiter = "tab".parse().unwrap();
+        let tracks = read("tabs.tsv", "title\tartist\tartist\tlength\nDrown\tBring Me The Horizon\tSomeone Else\t3:42\n", delimiter.0, &[]).unwrap();
+
+        assert_eq!(tracks[0].title, "Drown");
+        assert_eq!(tracks[0].artists, vec!["Bring Me The Horizon"]);
+        assert_eq!(tracks[0].duration, Some(222));
+    }
+
+    #[test]
+    fn maps_headers_over_aliases() {
+        let tracks = read("mapped.csv", "Artist,Performer,Song\nWrong,Motörhead,Ace of Spades\n", b',', &["artist=performer"]).unwrap();
+        assert_eq!(tracks[0].artist, "Motörhead");
+        assert_eq!(tracks[0].title, "Ace of Spades");
+
+        let err = read("unknown-mapping.csv", "Artist,Song\nMotörhead,Ace of Spades\n", b',', &["artist=Performer"]).unwrap_err();
+        assert!(err.to_string().contains("no Performer column"), "{}", err);
+        assert!("singer=Performer".parse::<ColumnMapping>().is_err());
+        assert!(read("no-track.csv", "Artist,Album\nMotörhead,Ace of Spades\n", b',', &[]).is_err());
+    }
+
+    #[test]
+    fn keeps_bad_rows_with_their_line() {
+        let tracks = read("bad-rows.csv", "Artist,Track,Spotify URI,Year\n\
+            Motörhead,Ace of Spades,,12/03/1980\n\
+            Motörhead\n\
+            ,,,\n\
+            Motörhead,Killed by Death,spotify:album:123,\n", b',', &[]).unwrap();
+
+        assert_eq!(tracks.len(), 4);
+        assert_eq!(tracks[0].error, None);
+        assert_eq!(tracks[0].year, None);
+        let errors: Vec<&str> = tracks[1..].iter().map(|track| track.error.as_deref().unwrap_or_default()).collect();
+        assert!(errors[0].starts_with("line 3: "), "{}", errors[0]);
+        assert_eq!(errors[1], "line 4: the row has neither a track nor a spotify uri");
+        assert_eq!(errors[2], "line 5: spotify:album:123 is not a spotify track uri");
+    }
+
+    #[test]
+    fn reads_spotify_uris_without_a_track() {
+        let tracks = read("uris.csv", "Track URI\nhttps://open.spotify.com/intl-de/track/4uLU6hMCjMI75M1A2tKUQC?si=1\n", b',', &[]).unwrap();
+        assert_eq!(tracks[0].spotify_uri.as_deref(), Some("spotify:track:4uLU6hMCjMI75M1A2tKUQC"));
+        assert_eq!(tracks[0].error, None);
+    }
+
+    #[test]
+    fn splits_artist_cells() {
+        let cases = vec![
+            ("Linkin Park; JAY-Z", vec!["Linkin Park", "JAY-Z"]),
+            ("Simon & Garfunkel", vec!["Simon", "Garfunkel"]),
+            ("Artist feat. X, Y", vec!["Artist", "X", "Y"]),
+            ("AC/DC", vec!["AC/DC"]),
+            (" ", vec![]),
+        ];
+
+        for (cell, expected) in cases {
+            assert_eq!(split_artists(cell), expected, "cell {:?}", cell);
+        }
+    }
+}
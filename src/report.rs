@@ -0,0 +1,122 @@
+use std::fs::File;
+use std::path::Path;
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::matcher::Match;
+use crate::provider::TrackDescriptor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchStatus {
+    Matched,
+    NotFound,
+    Ambiguous,
+    AlreadyInPlaylist,
+    Duplicate,
+    AddFailed,
+    /// The source could not read the track, it was never searched
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub uri: String,
+    pub name: String,
+    pub artists: Vec<String>,
+    pub score: f64,
+}
+
+impl From<&Match<'_>> for Candidate {
+    fn from(found: &Match) -> Self {
+        Candidate {
+            uri: found.track.uri.clone(),
+            name: found.track.name.clone(),
+            artists: found.track.artists.iter().map(|artist| artist.name.clone()).collect(),
+            score: found.score,
+        }
+    }
+}
+
+/// What happened to a single source track during the import
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackOutcome {
+    pub track: TrackDescriptor,
+    pub query: String,
+    pub status: MatchStatus,
+    pub uri: Option<String>,
+    pub score: Option<f64>,
+    pub runner_ups: Vec<Candidate>,
+    /// Whether the match came from the search cache instead of a spotify search
+    #[serde(default)]
+    pub cached: bool,
+}
+
+/// A flattened outcome, the artist and track columns line up with the raw importer. Only matched
+/// rows carry a uri so a report fed back in searches everything else again
+#[derive(Debug, Serialize)]
+struct ReportRow<'a> {
+    position: usize,
+    artist: &'a str,
+    track: &'a str,
+    album: Option<&'a str>,
+    isrc: Option<&'a str>,
+    status: MatchStatus,
+    uri: Option<&'a str>,
+    score: Option<f64>,
+    cached: bool,
+    runner_ups: String,
+    error: Option<&'a str>,
+}
+
+impl<'a> From<&'a TrackOutcome> for ReportRow<'a> {
+    fn from(outcome: &'a TrackOutcome) -> Self {
+        ReportRow {
+            position: outcome.track.position,
+            artist: &outcome.track.artist,
+            track: &outcome.track.title,
+            album: outcome.track.album.as_deref(),
+            isrc: outcome.track.isrc.as_deref(),
+            status: outcome.status,
+            uri: outcome.uri.as_deref().filter(|_| outcome.status == MatchStatus::Matched),
+            score: outcome.score,
+            cached: outcome.cached,
+            runner_ups: outcome.runner_ups.iter()
+                .map(|candidate| format!("{} ({:.2})", candidate.uri, candidate.score))
+                .collect::<Vec<String>>()
+                .join(";"),
+            error: outcome.track.error.as_deref(),
+        }
+    }
+}
+
+/// Prints how many tracks ended up with each status
+pub fn print_summary(outcomes: &[TrackOutcome]) {
+    let count = |status: MatchStatus| outcomes.iter().filter(|outcome| outcome.status == status).count();
+    let cached = outcomes.iter().filter(|outcome| outcome.cached && outcome.status == MatchStatus::Matched).count();
+    println!("> Matched {} tracks ({} from cache)", count(MatchStatus::Matched), cached);
+    println!("> Not found {}, ambiguous {}, already in playlist {}, duplicates {}, failed to add {}, unreadable {}",
+             count(MatchStatus::NotFound),
+             count(MatchStatus::Ambiguous),
+             count(MatchStatus::AlreadyInPlaylist),
+             count(MatchStatus::Duplicate),
+             count(MatchStatus::AddFailed),
+             count(MatchStatus::Invalid));
+}
+
+/// Writes every outcome to the path, as csv when the extension says so and json otherwise
+pub fn write_report(path: &Path, outcomes: &[TrackOutcome]) -> Result<(), Error> {
+    let is_csv = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    if is_csv {
+        let mut writer = csv::Writer::from_path(path)?;
+        for outcome in outcomes {
+            writer.serialize(ReportRow::from(outcome))?;
+        }
+        writer.flush()?;
+    } else {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, outcomes)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::{anyhow, Error};
+use rand::Rng;
+use rspotify::client::ApiError;
+use tokio::time::delay_for;
+
+use crate::rate_limit::RateLimiter;
+
+/// How often and how long to wait before giving up on a spotify call. Every attempt goes through
+/// the shared rate limiter first
+#[derive(Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub limiter: RateLimiter,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            limiter: RateLimiter::new(0),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, requests_per_second: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            limiter: RateLimiter::new(requests_per_second),
+            ..Default::default()
+        }
+    }
+
+    /// Runs the call until it succeeds, fails with an error not worth retrying or runs out of attempts.
+    /// Rate limits wait for as long as spotify asks, server and connection errors back off exponentially
+    pub async fn run<T, F, Fut>(&self, description: &str, call: F) -> Result<T, Error>
+        where F: FnMut() -> Fut,
+              Fut: Future<Output=Result<T, failure::Error>> {
+        self.run_with(description, true, call).await
+    }
+
+    /// Like `run` for calls that must not happen twice, like adding tracks or creating a playlist. Only
+    /// errors where the request provably never reached spotify are retried, a failed connection or a
+    /// rate limit. A timeout or server error may have come after spotify already did the work
+    pub async fn run_non_idempotent<T, F, Fut>(&self, description: &str, call: F) -> Result<T, Error>
+        where F: FnMut() -> Fut,
+              Fut: Future<Output=Result<T, failure::Error>> {
+        self.run_with(description, false, call).await
+    }
+
+    async fn run_with<T, F, Fut>(&self, description: &str, idempotent: bool, mut call: F) -> Result<T, Error>
+        where F: FnMut() -> Fut,
+              Fut: Future<Output=Result<T, failure::Error>> {
+        let mut attempt = 1;
+        loop {
+            self.limiter.acquire().await;
+            let err = match call().await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            let delay = match self.delay_for(&err, attempt, idempotent) {
+                Some(delay) if attempt < self.max_attempts => delay,
+                _ => return Err(anyhow!("{} failed after {} attempts because {}", description, attempt, err)),
+            };
+            println!("> {} failed because {}, retrying in {:.1}s..", description, err, delay.as_secs_f64());
+            delay_for(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// How long to wait before the next attempt, none when the error is not worth retrying. Calls that
+    /// are not idempotent only retry errors that never reached spotify
+    fn delay_for(&self, err: &failure::Error, attempt: u32, idempotent: bool) -> Option<Duration> {
+        if let Some(api_error) = err.downcast_ref::<ApiError>() {
+            return match api_error {
+                ApiError::RateLimited(Some(seconds)) => Some(Duration::from_secs(*seconds as u64 + 1)),
+                ApiError::RateLimited(None) => Some(self.backoff(attempt)),
+                ApiError::Other(status) if idempotent && *status >= 500 => Some(self.backoff(attempt)),
+                _ => None,
+            };
+        }
+        match err.downcast_ref::<reqwest::Error>() {
+            Some(err) if err.is_connect() => Some(self.backoff(attempt)),
+            Some(err) if idempotent && (err.is_timeout() || err.is_request()) => Some(self.backoff(attempt)),
+            _ => None,
+        }
+    }
+
+    /// Exponential backoff, jittered so concurrent retries spread out
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self.base_delay.saturating_mul(2u32.saturating_pow(attempt - 1));
+        let capped = exponential.min(self.max_delay);
+        let jitter = rand::thread_rng().gen_range(0.5, 1.0);
+        capped.mul_f64(jitter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retries_only_unsent_calls_that_are_not_idempotent() {
+        let retry = RetryPolicy::default();
+        let rate_limited = failure::Error::from(ApiError::RateLimited(Some(2)));
+        let server_error = failure::Error::from(ApiError::Other(502));
+        let not_found = failure::Error::from(ApiError::Other(404));
+
+        assert_eq!(retry.delay_for(&rate_limited, 1, true), Some(Duration::from_secs(3)));
+        assert_eq!(retry.delay_for(&rate_limited, 1, false), Some(Duration::from_secs(3)));
+        assert!(retry.delay_for(&server_error, 1, true).is_some());
+        assert_eq!(retry.delay_for(&server_error, 1, false), None);
+        assert_eq!(retry.delay_for(&not_found, 1, true), None);
+    }
+}
@@ -1,17 +1,16 @@
-use std::fs::File;
+use std::collections::HashSet;
+use std::fs::{self, File};
 use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::Error;
 use async_trait::async_trait;
-use rspotify::model::{search::SearchResult, track::FullTrack};
-use rspotify::oauth2::{SpotifyClientCredentials, SpotifyOAuth, TokenInfo};
-use rspotify::senum::{Country, IncludeExternal, SearchType};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
 use crate::cli::Opts;
-use crate::provider::StreamingProvider;
+use crate::provider::{StreamingProvider, TrackDescriptor};
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -26,8 +25,12 @@ pub struct Tidal {
 #[serde(rename_all = "camelCase")]
 pub struct Track {
     pub item: TrackDetails,
-    #[serde(rename = "type")]
+    // only favourites pages have this, playlist pages have type and cut instead
+    #[serde(default)]
+    pub created: Option<String>,
+    #[serde(rename = "type", default)]
     pub type_field: String,
+    #[serde(default)]
     pub cut: Value,
 }
 
@@ -48,6 +51,8 @@ pub struct TrackDetails {
     pub version: Option<String>,
     pub popularity: i64,
     pub copyright: String,
+    // description, date_added, index and item_uuid only come with playlist items
+    #[serde(default)]
     pub description: Value,
     pub url: String,
     pub isrc: String,
@@ -59,9 +64,12 @@ pub struct TrackDetails {
     pub artists: Vec<Artist2>,
     pub album: Album,
     pub mixes: Mixes,
-    pub date_added: String,
-    pub index: i64,
-    pub item_uuid: String,
+    #[serde(default)]
+    pub date_added: Option<String>,
+    #[serde(default)]
+    pub index: Option<i64>,
+    #[serde(default)]
+    pub item_uuid: Option<String>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -101,18 +109,47 @@ pub struct Mixes {
     pub master_track_mix: Option<String>,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TidalAlbums {
+    pub limit: i64,
+    pub offset: i64,
+    pub total_number_of_items: i64,
+    pub items: Vec<FavouriteAlbum>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FavouriteAlbum {
+    pub created: String,
+    pub item: AlbumDetails,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlbumDetails {
+    pub id: i64,
+    pub title: String,
+    pub duration: i64,
+    pub number_of_tracks: i64,
+    pub release_date: Option<String>,
+    pub version: Option<String>,
+    pub url: String,
+    pub explicit: bool,
+    pub upc: Option<String>,
+    pub artist: Artist,
+    pub artists: Vec<Artist2>,
+}
 
 #[derive(Default, Debug, Clone)]
 pub struct TidalProvider {
-    pub playlist: String,
-    pub file: PathBuf,
+    pub files: Vec<PathBuf>,
 }
 
 impl TidalProvider {
     pub fn new(opts: &Opts) -> TidalProvider {
         TidalProvider {
-            playlist: opts.playlist.to_owned(),
-            file: opts.tidal_file.as_ref().expect("Failed to unwrap the tidal_file parameter").to_path_buf(),
+            files: opts.tidal_file.clone(),
         }
     }
 }
@@ -121,36 +158,162 @@ impl TidalProvider {
 impl StreamingProvider<Tidal> for TidalProvider {
     async fn gather_data(&self) -> Result<Tidal, Error> {
         println!("> Reading tidal file..");
-        let tidal = get_tidal_from_file(&self.file).await?;
+        let tidal = get_tidal_from_files(&self.files).await?;
         // read a source
-        println!("> Importing {} tracks..", tidal.items.len() - 1);
+        println!("> Importing {} tracks..", tidal.items.len());
         Ok(tidal)
     }
 
-    fn convert_to_query(&self, item: Tidal) -> Vec<(String, String)> {
-        println!("> Converting to query..");
-        // convert items to title with artists
-        item.items.iter()
-            .map(|track| {
-                let artist: String = track.item.artists.iter().map(|artist| artist.name.to_lowercase()).collect::<Vec<String>>().join(" ");
-                let query: String = vec![artist, track.item.title.to_lowercase()].join(" ");
-                (track.item.artist.name.to_lowercase(), query)
-            }).collect()
+    fn convert_to_query(&self, item: Tidal) -> Vec<TrackDescriptor> {
+        convert_tidal_tracks(item)
     }
 
-    async fn build_queries(&self) -> Result<Vec<(String, String)>, Error> {
+    async fn build_queries(&self) -> Result<Vec<TrackDescriptor>, Error> {
         let tidal = self.gather_data().await?;
         let queries = self.convert_to_query(tidal);
         Ok(queries)
     }
 }
 
-pub async fn get_tidal_from_file(path: &PathBuf) -> Result<Tidal, Error> {
+/// A single page of a tidal api listing, several of them are merged into one import
+pub trait TidalPage: DeserializeOwned {
+    type Item;
+    fn limit(&self) -> i64;
+    fn offset(&self) -> i64;
+    fn item_count(&self) -> usize;
+    fn total_number_of_items(&self) -> i64;
+    fn items(self) -> Vec<Self::Item>;
+    fn item_id(item: &Self::Item) -> i64;
+    fn from_items(items: Vec<Self::Item>, total_number_of_items: i64) -> Self;
+
+    /// Where the page starts. A page holding more items than its limit is a whole export rather
+    /// than a single api page, so its offset says nothing and it is taken to start at the beginning
+    fn start(&self) -> i64 {
+        if self.item_count() as i64 > self.limit() { 0 } else { self.offset() }
+    }
+}
+
+impl TidalPage for Tidal {
+    type Item = Track;
+    fn limit(&self) -> i64 { self.limit }
+    fn offset(&self) -> i64 { self.offset }
+    fn item_count(&self) -> usize { self.items.len() }
+    fn total_number_of_items(&self) -> i64 { self.total_number_of_items }
+    fn items(self) -> Vec<Track> { self.items }
+    fn item_id(item: &Track) -> i64 { item.item.id }
+    fn from_items(items: Vec<Track>, total_number_of_items: i64) -> Tidal {
+        Tidal { limit: items.len() as i64, offset: 0, total_number_of_items, items }
+    }
+}
+
+impl TidalPage for TidalAlbums {
+    type Item = FavouriteAlbum;
+    fn limit(&self) -> i64 { self.limit }
+    fn offset(&self) -> i64 { self.offset }
+    fn item_count(&self) -> usize { self.items.len() }
+    fn total_number_of_items(&self) -> i64 { self.total_number_of_items }
+    fn items(self) -> Vec<FavouriteAlbum> { self.items }
+    fn item_id(item: &FavouriteAlbum) -> i64 { item.item.id }
+    fn from_items(items: Vec<FavouriteAlbum>, total_number_of_items: i64) -> TidalAlbums {
+        TidalAlbums { limit: items.len() as i64, offset: 0, total_number_of_items, items }
+    }
+}
+
+pub fn convert_tidal_tracks(tidal: Tidal) -> Vec<TrackDescriptor> {
+    println!("> Converting to query..");
+    tidal.items.iter()
+        .enumerate()
+        .map(|(position, track)| {
+            let details = &track.item;
+            TrackDescriptor {
+                title: details.title.clone(),
+                artist: details.artist.name.clone(),
+                artists: details.artists.iter().map(|artist| artist.name.clone()).collect(),
+                album: Some(details.album.title.clone()),
+                duration: Some(details.duration as u32),
+                approximate_duration: false,
+                isrc: Some(details.isrc.clone()).filter(|isrc| !isrc.is_empty()),
+                explicit: Some(details.explicit),
+                source_id: Some(details.id.to_string()),
+                position,
+                spotify_uri: None,
+                year: details.album.release_date.get(..4).and_then(|year| year.parse().ok()),
+                error: None,
+            }
+        }).collect()
+}
+
+pub async fn get_tidal_from_files(paths: &[PathBuf]) -> Result<Tidal, Error> {
+    get_pages_from_files(paths)
+}
+
+pub async fn get_tidal_albums_from_files(paths: &[PathBuf]) -> Result<TidalAlbums, Error> {
+    get_pages_from_files(paths)
+}
+
+/// Reads every page, directories contribute each json file inside them, and merges them in offset order
+fn get_pages_from_files<P: TidalPage>(paths: &[PathBuf]) -> Result<P, Error> {
+    let mut pages = vec![];
+    for path in expand_paths(paths)? {
+        pages.push(get_page_from_file::<P>(&path)?);
+    }
+    merge_pages(pages)
+}
+
+/// Sorts the pages by where they start, warns about gaps against the total and drops items that show up twice
+pub fn merge_pages<P: TidalPage>(mut pages: Vec<P>) -> Result<P, Error> {
+    if pages.is_empty() {
+        return Err(anyhow::anyhow!("No tidal pages to import"));
+    }
+    pages.sort_by_key(|page| page.start());
+
+    let total = pages.iter().map(|page| page.total_number_of_items()).max().unwrap_or_default();
+    if pages.iter().any(|page| page.total_number_of_items() != total) {
+        println!("> Warning, the pages disagree on the total number of items, using {}", total);
+    }
+
+    let mut seen = HashSet::new();
+    let mut items = vec![];
+    let mut covered = 0;
+    for page in pages {
+        let offset = page.start();
+        if offset > covered {
+            println!("> Warning, missing items {} to {}", covered, offset - 1);
+        }
+        let page_items = page.items();
+        covered = covered.max(offset + page_items.len() as i64);
+        items.extend(page_items.into_iter().filter(|item| seen.insert(P::item_id(item))));
+    }
+    if covered < total {
+        println!("> Warning, missing items {} to {}", covered, total - 1);
+    }
+
+    Ok(P::from_items(items, total))
+}
+
+fn expand_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Error> {
+    let mut files = vec![];
+    for path in paths {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|entry| entry.extension().is_some_and(|extension| extension == "json"))
+                .collect();
+            entries.sort();
+            files.append(&mut entries);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+fn get_page_from_file<P: DeserializeOwned>(path: &Path) -> Result<P, Error> {
     let file = File::open(path)?;
     let reader = BufReader::new(file);
-    let result: Result<Tidal, _> = serde_json::from_reader(reader);
+    let result: Result<P, _> = serde_json::from_reader(reader);
     match result {
         Ok(val) => Ok(val),
-        Err(err) => Err(anyhow::anyhow!(format!("Some issue {}", err)))
+        Err(err) => Err(anyhow::anyhow!(format!("Some issue with {} {}", path.display(), err)))
     }
 }
@@ -0,0 +1,162 @@
+use anyhow::{anyhow, Error};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, AUTHORIZATION};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::Opts;
+use crate::provider::{StreamingProvider, TrackDescriptor};
+use crate::tidal::{convert_tidal_tracks, merge_pages, Tidal};
+
+const PAGE_SIZE: i64 = 100;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    pub user_id: i64,
+    pub country_code: String,
+}
+
+/// Reads a playlist or the user's favourite tracks straight from the tidal api
+#[derive(Default, Debug, Clone)]
+pub struct TidalApiProvider {
+    pub base_url: String,
+    pub playlist: String,
+    pub token: String,
+}
+
+impl TidalApiProvider {
+    pub fn new(opts: &Opts) -> TidalApiProvider {
+        TidalApiProvider {
+            base_url: opts.tidal_api.trim_end_matches('/').to_string(),
+            playlist: opts.tidal_playlist.as_ref().expect("Failed to unwrap the tidal_playlist parameter").to_string(),
+            token: opts.tidal_token.as_ref().expect("Failed to unwrap the tidal_token parameter").to_string(),
+        }
+    }
+
+    fn client(&self) -> Result<Client, Error> {
+        let mut header_map = HeaderMap::new();
+        header_map.insert(AUTHORIZATION, format!("Bearer {}", self.token).parse()?);
+        Ok(Client::builder().default_headers(header_map).build()?)
+    }
+
+    fn items_url(&self, session: &Session) -> String {
+        match self.playlist.as_str() {
+            "favourites" | "favorites" => format!("{}/users/{}/favorites/tracks", self.base_url, session.user_id),
+            playlist => format!("{}/playlists/{}/items", self.base_url, playlist),
+        }
+    }
+}
+
+#[async_trait]
+impl StreamingProvider<Tidal> for TidalApiProvider {
+    async fn gather_data(&self) -> Result<Tidal, Error> {
+        let client = self.client()?;
+        println!("> Getting tidal session..");
+        let session: Session = get_json(client.get(&format!("{}/sessions", self.base_url))).await?;
+
+        let url = self.items_url(&session);
+        let mut pages = vec![];
+        let mut offset = 0;
+        loop {
+            println!("> Retrieving tidal page at {}..", offset);
+            let request = client.get(&url)
+                .query(&[("limit", PAGE_SIZE.to_string()), ("offset", offset.to_string()), ("countryCode", session.country_code.clone())]);
+            let page: Tidal = get_json(request).await?;
+            let fetched = page.items.len() as i64;
+            let total = page.total_number_of_items;
+            pages.push(page);
+            offset += fetched;
+            if fetched == 0 || offset >= total {
+                break;
+            }
+        }
+
+        let tidal = merge_pages(pages)?;
+        println!("> Importing {} tracks..", tidal.items.len());
+        Ok(tidal)
+    }
+
+    fn convert_to_query(&self, item: Tidal) -> Vec<TrackDescriptor> {
+        convert_tidal_tracks(item)
+    }
+
+    async fn build_queries(&self) -> Result<Vec<TrackDescriptor>, Error> {
+        let tidal = self.gather_data().await?;
+        let queries = self.convert_to_query(tidal);
+        Ok(queries)
+    }
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(request: reqwest::RequestBuilder) -> Result<T, Error> {
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Tidal responded with {} {}", status, body));
+    }
+    Ok(response.json().await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::{mock, Matcher, Mock};
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn deathcore_items() -> Vec<Value> {
+        let page: Value = serde_json::from_str(&std::fs::read_to_string("tidal-tracks-deathcore.json").unwrap()).unwrap();
+        page["items"].as_array().unwrap().clone()
+    }
+
+    /// Favourites items come as `{created, item}` without the playlist-only fields
+    fn as_favourites(items: Vec<Value>) -> Vec<Value> {
+        items.into_iter().map(|item| {
+            let mut details = item["item"].clone();
+            let details_map = details.as_object_mut().unwrap();
+            for field in &["description", "dateAdded", "index", "itemUuid"] {
+                details_map.remove(*field);
+            }
+            json!({ "created": "2020-11-20T19:02:13.563+0000", "item": details })
+        }).collect()
+    }
+
+    fn mock_pages(path: &str, items: &[Value]) -> Vec<Mock> {
+        items.chunks(PAGE_SIZE as usize)
+            .enumerate()
+            .map(|(page, chunk)| {
+                let offset = page as i64 * PAGE_SIZE;
+                let body = json!({ "limit": PAGE_SIZE, "offset": offset, "totalNumberOfItems": items.len(), "items": chunk });
+                mock("GET", path)
+                    .match_query(Matcher::AllOf(vec![
+                        Matcher::UrlEncoded("offset".into(), offset.to_string()),
+                        Matcher::UrlEncoded("countryCode".into(), "GB".into()),
+                    ]))
+                    .match_header("authorization", "Bearer token")
+                    .with_body(body.to_string())
+                    .create()
+            }).collect()
+    }
+
+    fn provider(playlist: &str) -> TidalApiProvider {
+        TidalApiProvider { base_url: mockito::server_url(), playlist: playlist.to_string(), token: "token".to_string() }
+    }
+
+    #[tokio::test]
+    async fn reads_playlists_and_favourites() {
+        let _session = mock("GET", "/sessions")
+            .with_body(json!({ "userId": 42, "countryCode": "GB" }).to_string())
+            .create();
+        let items = deathcore_items();
+        let _playlist = mock_pages("/playlists/deathcore/items", &items);
+        let _favourites = mock_pages("/users/42/favorites/tracks", &as_favourites(items.clone()));
+
+        let playlist = provider("deathcore").build_queries().await.unwrap();
+        assert_eq!(playlist.len(), items.len());
+
+        let favourites = provider("favourites").build_queries().await.unwrap();
+        assert_eq!(favourites.len(), items.len());
+        assert_eq!(favourites, playlist);
+    }
+}
@@ -0,0 +1,283 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::matcher::similarity;
+
+/// Bits of video titles that say nothing about the song itself
+const NOISE_PATTERNS: [&str; 11] = [
+    r"\bofficial\s+(?:music\s+|lyrics?\s+)?(?:video|audio|visuali[sz]er)\b",
+    r"\blyrics?(?:\s+video)?\b",
+    r"\bvisuali[sz]er\b",
+    r"\b(?:hd|hq|4k|1080p|720p)\b",
+    r"\bremaster(?:ed)?\b",
+    r"\bfull\s+album\b",
+    r"^\s*audio\s*$",
+    r"^\s*video\s*$",
+    r"^\s*m/?v\s*$",
+    r"^\s*explicit\s*$",
+    r"^\s*clean\s*$",
+];
+
+/// How similar a name from the title has to be to the channel to count as the same artist
+const CHANNEL_SIMILARITY: f64 = 0.8;
+
+/// The artist and song read out of a video title
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ParsedTitle {
+    /// The primary artist, empty when neither the title nor the channel could tell us
+    pub artist: String,
+    pub title: String,
+    pub featured: Vec<String>,
+}
+
+impl ParsedTitle {
+    /// The primary artist followed by everyone featured
+    pub fn artists(&self) -> Vec<String> {
+        let mut artists = vec![];
+        if !self.artist.is_empty() {
+            artists.push(self.artist.clone());
+        }
+        artists.extend(self.featured.iter().cloned());
+        artists
+    }
+}
+
+/// Splits a video title into artist and song, dropping noise like "(Official Video)" or "| Lyrics".
+/// The uploading channel, with "- Topic" or "VEVO" stripped, stands in when the title names no artist
+/// and is checked against the artist the title names when it does
+pub fn parse_title(raw: &str, channel: Option<&str>) -> ParsedTitle {
+    let topic_channel = channel.is_some_and(is_topic_channel);
+    let channel = channel.map(channel_artist).filter(|channel| !channel.is_empty());
+    let mut featured = vec![];
+    let cleaned = strip_noise(raw, &mut featured);
+
+    let (artist, title) = split_artist(&cleaned, channel.as_deref());
+    let (artist, title) = match &channel {
+        Some(channel) => check_against_channel(artist, title, channel, topic_channel, &cleaned),
+        None => (artist, title),
+    };
+    let (artist, mut artist_featured) = split_featured(&artist);
+    let (title, mut title_featured) = split_featured(&title);
+    featured.append(&mut artist_featured);
+    featured.append(&mut title_featured);
+
+    let title = if title.is_empty() { collapse_whitespace(raw) } else { title };
+    ParsedTitle { artist, title, featured }
+}
+
+/// The artist name behind a channel, "Artist - Topic" and "ArtistVEVO" become "Artist"
+pub fn channel_artist(channel: &str) -> String {
+    let channel = channel.trim();
+    let channel = channel.strip_suffix("- Topic").unwrap_or(channel);
+    let channel = channel.strip_suffix("VEVO").unwrap_or(channel);
+    channel.trim().to_string()
+}
+
+lazy_static! {
+    static ref NOISE: Regex = Regex::new(&format!("(?i){}", NOISE_PATTERNS.join("|"))).unwrap();
+}
+
+/// Auto generated "- Topic" channels only ever upload their own artist and carry its exact name.
+/// VEVO channels squash the name together, "ArcticMonkeysVEVO", so their spelling is never preferred
+fn is_topic_channel(channel: &str) -> bool {
+    channel.trim().ends_with("- Topic")
+}
+
+/// Settles on an artist when the title and the channel both name one. A title artist matching a topic
+/// channel takes the channel's spelling, a title written song first is flipped around and anything a
+/// topic channel uploads is taken to be theirs with the whole title as the song. Otherwise the title's
+/// own split wins
+fn check_against_channel(artist: String, song: String, channel: &str, topic_channel: bool, cleaned: &str) -> (String, String) {
+    let (primary, _) = split_featured(&artist);
+    if similarity(&primary, channel) >= CHANNEL_SIMILARITY {
+        let artist = if topic_channel { artist.replacen(&primary, channel, 1) } else { artist };
+        return (artist, song);
+    }
+    if similarity(&song, channel) >= CHANNEL_SIMILARITY {
+        return (song, artist);
+    }
+    if topic_channel {
+        return (channel.to_string(), cleaned.to_string());
+    }
+    (artist, song)
+}
+
+fn contains_noise(text: &str) -> bool {
+    NOISE.is_match(text)
+}
+
+/// Whether nothing but noise is left once the noise is taken out
+fn is_only_noise(text: &str) -> bool {
+    !NOISE.replace_all(text, "").chars().any(|c| c.is_alphanumeric())
+}
+
+/// Drops "| ..." suffixes and bracketed noise, featured artists in brackets are moved to `featured`
+fn strip_noise(raw: &str, featured: &mut Vec<String>) -> String {
+    lazy_static! {
+        static ref BRACKETS: Regex = Regex::new(r"\([^()]*\)|\[[^\[\]]*\]|【[^【】]*】").unwrap();
+        static ref FEATURING: Regex = Regex::new(r"(?i)^(?:ft\.?|feat\.?|featuring)\s+(.+)$").unwrap();
+        static ref TRAILING_NOISE: Regex = Regex::new(r"(?i)\s+(?:official\s+(?:music\s+)?(?:video|audio)|lyric\s+video|hd|hq|4k)\s*$").unwrap();
+    }
+    let segment = raw.split('|')
+        .map(|segment| segment.trim())
+        .find(|segment| !is_only_noise(segment))
+        .unwrap_or_else(|| raw.trim());
+
+    let without_brackets = BRACKETS.replace_all(segment, |captures: &regex::Captures| {
+        let group = &captures[0];
+        let inner = group.trim_start_matches(['(', '[', '【']).trim_end_matches([')', ']', '】']).trim();
+        if let Some(names) = FEATURING.captures(inner) {
+            featured.extend(split_names(&names[1]));
+            String::new()
+        } else if contains_noise(inner) {
+            String::new()
+        } else {
+            group.to_string()
+        }
+    });
+
+    let mut cleaned = collapse_whitespace(&without_brackets);
+    while let Some(found) = TRAILING_NOISE.find(&cleaned) {
+        cleaned.truncate(found.start());
+    }
+    cleaned
+}
+
+/// Finds the artist by separator, "by" or quotes, in that order, and falls back to the channel
+fn split_artist(title: &str, channel: Option<&str>) -> (String, String) {
+    lazy_static! {
+        // a dash needs whitespace on at least one side so hyphenated names stay whole
+        static ref SEPARATOR: Regex = Regex::new(r"\s+[-–—~]+\s*|[-–—~]+\s+").unwrap();
+        static ref BY: Regex = Regex::new(r"(?i)^(.+?)\s+by\s+(.+)$").unwrap();
+        static ref QUOTED: Regex = Regex::new(r#"^(.*?)\s*["“”]([^"“”]+)["“”]\s*(.*)$"#).unwrap();
+    }
+    let fallback = || (channel.unwrap_or_default().to_string(), title.trim().to_string());
+
+    if let Some(found) = SEPARATOR.find(title) {
+        let artist = title[..found.start()].trim();
+        let song = title[found.end()..].trim();
+        if !artist.is_empty() && !song.is_empty() {
+            return (artist.to_string(), song.to_string());
+        }
+    }
+    if let Some(captures) = BY.captures(title) {
+        let artist = captures[2].trim();
+        let trusted = channel.is_none_or(|channel| similarity(artist, channel) >= CHANNEL_SIMILARITY);
+        if trusted {
+            return (artist.to_string(), captures[1].trim().to_string());
+        }
+        return fallback();
+    }
+    if let Some(captures) = QUOTED.captures(title) {
+        let artist = captures[1].trim();
+        let song = captures[2].trim();
+        if !song.is_empty() {
+            let artist = if artist.is_empty() { channel.unwrap_or_default() } else { artist };
+            return (artist.to_string(), song.to_string());
+        }
+    }
+    fallback()
+}
+
+/// Splits "Artist ft. Other" into the artist and the names featured
+fn split_featured(text: &str) -> (String, Vec<String>) {
+    lazy_static! {
+        static ref FEATURING: Regex = Regex::new(r"(?i)\s+(?:ft\.?|feat\.?|featuring)\s+").unwrap();
+    }
+    match FEATURING.find(text) {
+        Some(found) => (text[..found.start()].trim().to_string(), split_names(&text[found.end()..])),
+        None => (text.trim().to_string(), vec![]),
+    }
+}
+
+fn split_names(names: &str) -> Vec<String> {
+    names.split([',', '&'])
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .map(|name| name.to_string())
+        .collect()
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(artist: &str, title: &str, featured: &[&str]) -> ParsedTitle {
+        ParsedTitle {
+            artist: artist.to_string(),
+            title: title.to_string(),
+            featured: featured.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn parses_titles() {
+        let cases = vec![
+            ("Artist - Song (Official Video)", None, parsed("Artist", "Song", &[])),
+            ("Artist – Song", None, parsed("Artist", "Song", &[])),
+            ("Artist — Song [Official Music Video]", None, parsed("Artist", "Song", &[])),
+            ("Artist -- Song", None, parsed("Artist", "Song", &[])),
+            ("Song by Artist", None, parsed("Artist", "Song", &[])),
+            ("Stand by Me", Some("Ben E. King - Topic"), parsed("Ben E. King", "Stand by Me", &[])),
+            ("Song by Artist", Some("Artist"), parsed("Artist", "Song", &[])),
+            ("Artist \"Song\"", None, parsed("Artist", "Song", &[])),
+            ("Artist “Song” (Lyric Video)", None, parsed("Artist", "Song", &[])),
+            ("Artist ft. X - Song | Lyrics", None, parsed("Artist", "Song", &["X"])),
+            ("Artist - Song (feat. X & Y) [4K]", None, parsed("Artist", "Song", &["X", "Y"])),
+            ("Artist - Song feat. X", None, parsed("Artist", "Song", &["X"])),
+            ("Blink-182 - All the Small Things", None, parsed("Blink-182", "All the Small Things", &[])),
+            ("Jay-Z - Song HD", None, parsed("Jay-Z", "Song", &[])),
+            ("Artist - Song (Live) [Remastered 2011]", None, parsed("Artist", "Song (Live)", &[])),
+            ("Artist - Song (Live at Lyrical Fest)", None, parsed("Artist", "Song (Live at Lyrical Fest)", &[])),
+            ("Artist - Song [Unremastered Take]", None, parsed("Artist", "Song [Unremastered Take]", &[])),
+            ("Artist - Album (Full Album)", None, parsed("Artist", "Album", &[])),
+            ("Artist - Song 【MV】", None, parsed("Artist", "Song", &[])),
+            ("Lyrics | Artist - Song", None, parsed("Artist", "Song", &[])),
+            ("HD | Artist - Song (Remastered)", None, parsed("Artist", "Song", &[])),
+            ("Song", Some("Artist - Topic"), parsed("Artist", "Song", &[])),
+            ("Song (Official Audio)", Some("ArtistVEVO"), parsed("Artist", "Song", &[])),
+            ("Song [HD]", None, parsed("", "Song", &[])),
+            ("- Song", Some("Artist"), parsed("Artist", "- Song", &[])),
+            ("Song - Remix", Some("Artist - Topic"), parsed("Artist", "Song - Remix", &[])),
+            ("Artist - Song", Some("Artist - Topic"), parsed("Artist", "Song", &[])),
+            ("artist - Song", Some("Artist - Topic"), parsed("Artist", "Song", &[])),
+            ("Artist ft. X - Song", Some("ArtistVEVO"), parsed("Artist", "Song", &["X"])),
+            ("Arctic Monkeys - Do I Wanna Know? (Official Video)", Some("ArcticMonkeysVEVO"), parsed("Arctic Monkeys", "Do I Wanna Know?", &[])),
+            ("The Weeknd - Blinding Lights (Official Video)", Some("TheWeekndVEVO"), parsed("The Weeknd", "Blinding Lights", &[])),
+            ("Bring Me The Horizon - Drown (Official Video)", Some("BMTHOfficialVEVO"), parsed("Bring Me The Horizon", "Drown", &[])),
+            ("Song - Remix", Some("ArtistVEVO"), parsed("Song", "Remix", &[])),
+            ("Song - Artist", Some("Artist"), parsed("Artist", "Song", &[])),
+            ("Artist - Song", Some("Lyrics Uploads"), parsed("Artist", "Song", &[])),
+            ("(Official Video)", None, parsed("", "(Official Video)", &[])),
+            ("", None, parsed("", "", &[])),
+        ];
+
+        for (title, channel, expected) in cases {
+            assert_eq!(parse_title(title, channel), expected, "parsing {:?} from {:?}", title, channel);
+        }
+    }
+
+    #[test]
+    fn strips_channel_suffixes() {
+        let cases = vec![
+            ("Artist - Topic", "Artist"),
+            ("ArtistVEVO", "Artist"),
+            ("  Artist  ", "Artist"),
+            ("Topic", "Topic"),
+        ];
+
+        for (channel, expected) in cases {
+            assert_eq!(channel_artist(channel), expected, "channel {:?}", channel);
+        }
+    }
+
+    #[test]
+    fn lists_primary_then_featured_artists() {
+        assert_eq!(parsed("Artist", "Song", &["X"]).artists(), vec!["Artist", "X"]);
+        assert_eq!(parsed("", "Song", &["X"]).artists(), vec!["X"]);
+    }
+}
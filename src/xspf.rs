@@ -0,0 +1,183 @@
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Error};
+use async_trait::async_trait;
+use roxmltree::{Document, Node};
+
+use crate::cli::Opts;
+use crate::playlist_file::track_from_path;
+use crate::provider::{StreamingProvider, TrackDescriptor};
+use crate::raw::parse_track_uri;
+
+/// A `<track>` of an xspf playlist, every element is optional in the format
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct XspfTrack {
+    pub location: Option<String>,
+    pub identifiers: Vec<String>,
+    pub title: Option<String>,
+    pub creator: Option<String>,
+    pub album: Option<String>,
+    /// Length in milliseconds
+    pub duration: Option<u64>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Xspf {
+    pub tracks: Vec<XspfTrack>,
+}
+
+/// Reads xspf playlists as exported by desktop players and self hosted servers
+#[derive(Default, Debug, Clone)]
+pub struct XspfProvider {
+    pub file: PathBuf,
+}
+
+impl XspfProvider {
+    pub fn new(opts: &Opts) -> XspfProvider {
+        XspfProvider {
+            file: opts.playlist_file.as_ref().expect("Failed to unwrap the playlist_file parameter").to_path_buf(),
+        }
+    }
+}
+
+#[async_trait]
+impl StreamingProvider<Xspf> for XspfProvider {
+    async fn gather_data(&self) -> Result<Xspf, Error> {
+        println!("> Reading xspf file..");
+        let xspf = get_xspf_from_file(&self.file)?;
+        println!("> Importing {} tracks..", xspf.tracks.len());
+        Ok(xspf)
+    }
+
+    fn convert_to_query(&self, item: Xspf) -> Vec<TrackDescriptor> {
+        item.tracks.into_iter().enumerate().map(|(position, track)| {
+            let XspfTrack { location, identifiers, title, creator, album, duration } = track;
+            // without a title the file name is the best we have, often "Artist - Title"
+            let (artist, title) = match title {
+                Some(title) => (creator.unwrap_or_default(), title),
+                None => {
+                    let (path_artist, title) = location.as_deref().map(track_from_path).unwrap_or_default();
+                    (creator.or(path_artist).unwrap_or_default(), title)
+                }
+            };
+            TrackDescriptor {
+                title,
+                artists: if artist.is_empty() { vec![] } else { vec![artist.clone()] },
+                artist,
+                album,
+                duration: duration.map(|millis| (millis / 1000) as u32),
+                isrc: identifiers.iter().find_map(|identifier| parse_isrc(identifier)),
+                spotify_uri: identifiers.iter().find_map(|identifier| parse_track_uri(identifier)),
+                source_id: location.or_else(|| identifiers.first().cloned()),
+                position,
+                ..Default::default()
+            }
+        }).collect()
+    }
+
+    async fn build_queries(&self) -> Result<Vec<TrackDescriptor>, Error> {
+        let xspf = self.gather_data().await?;
+        let queries = self.convert_to_query(xspf);
+        Ok(queries)
+    }
+}
+
+pub fn get_xspf_from_file(path: &PathBuf) -> Result<Xspf, Error> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read xspf file {}", path.display()))?;
+    let document = Document::parse(&contents)
+        .map_err(|err| anyhow!("Some issue with {} {}", path.display(), err))?;
+    let track_list = document.root_element().children()
+        .find(|node| node.has_tag_name("trackList"))
+        .ok_or_else(|| anyhow!("{} has no trackList", path.display()))?;
+
+    let tracks = track_list.children()
+        .filter(|node| node.has_tag_name("track"))
+        .map(|track| XspfTrack {
+            location: child_text(track, "location"),
+            identifiers: track.children()
+                .filter(|node| node.has_tag_name("identifier"))
+                .filter_map(|node| node.text().map(|text| text.trim().to_string()))
+                .collect(),
+            title: child_text(track, "title"),
+            creator: child_text(track, "creator"),
+            album: child_text(track, "album"),
+            duration: child_text(track, "duration").and_then(|duration| duration.parse().ok()),
+        })
+        .collect();
+    Ok(Xspf { tracks })
+}
+
+/// The trimmed text of the first child with the tag, none when it is missing or empty
+fn child_text(node: Node, tag: &str) -> Option<String> {
+    node.children()
+        .find(|child| child.has_tag_name(tag))
+        .and_then(|child| child.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// Identifiers carry isrcs as isrc:CODE or urn:isrc:CODE
+fn parse_isrc(identifier: &str) -> Option<String> {
+    let lower = identifier.to_lowercase();
+    let start = lower.find("isrc:")? + "isrc:".len();
+    Some(identifier[start..].trim().to_uppercase()).filter(|isrc| !isrc.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_tracks() {
+        let xspf = get_xspf_from_file(&PathBuf::from("example.xspf")).unwrap();
+        let tracks = XspfProvider::default().convert_to_query(xspf);
+
+        assert_eq!(tracks, vec![
+            TrackDescriptor {
+                title: "Agony".to_string(),
+                artist: "Slaughter to Prevail".to_string(),
+                artists: vec!["Slaughter to Prevail".to_string()],
+                album: Some("Kostolom".to_string()),
+                duration: Some(210),
+                isrc: Some("GBUMV2000001".to_string()),
+                source_id: Some("file:///music/Slaughter%20to%20Prevail/Kostolom/01%20Agony.flac".to_string()),
+                ..Default::default()
+            },
+            TrackDescriptor {
+                title: "Never Gonna Give You Up".to_string(),
+                artist: "Rick Astley".to_string(),
+                artists: vec!["Rick Astley".to_string()],
+                spotify_uri: Some("spotify:track:4uLU6hMCjMI75M1A2tKUQC".to_string()),
+                source_id: Some("spotify:track:4uLU6hMCjMI75M1A2tKUQC".to_string()),
+                position: 1,
+                ..Default::default()
+            },
+            TrackDescriptor {
+                title: "Killed by Death".to_string(),
+                artist: "Motörhead".to_string(),
+                artists: vec!["Motörhead".to_string()],
+                duration: Some(276),
+                source_id: Some("file:///music/Downloads/Motörhead%20-%20Killed%20by%20Death.mp3".to_string()),
+                position: 2,
+                ..Default::default()
+            },
+            TrackDescriptor {
+                title: "Stand by Me".to_string(),
+                artist: "Ben E. King".to_string(),
+                artists: vec!["Ben E. King".to_string()],
+                source_id: Some("http://example.com/stream/Stand%20by%20Me.ogg".to_string()),
+                position: 3,
+                ..Default::default()
+            },
+        ]);
+    }
+
+    #[test]
+    fn reads_isrc_identifiers() {
+        assert_eq!(parse_isrc("isrc:usrc17607839").as_deref(), Some("USRC17607839"));
+        assert_eq!(parse_isrc("urn:ISRC:USRC17607839").as_deref(), Some("USRC17607839"));
+        assert_eq!(parse_isrc("spotify:track:4uLU6hMCjMI75M1A2tKUQC"), None);
+    }
+}
@@ -0,0 +1,383 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Error};
+use async_trait::async_trait;
+use json_dotpath::DotPaths;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::cli::Opts;
+use crate::provider::{parse_duration, StreamingProvider, TrackDescriptor};
+use crate::youtube::{build_client, read_cookie_file, sign_request, InnertubeConfig};
+
+const MUSIC_URL: &str = "https://music.youtube.com";
+
+/// Where the playlist shelf sits in the responses youtube music has been seen to send
+const SHELF_PATHS: [&str; 4] = [
+    "contents.singleColumnBrowseResultsRenderer.tabs.0.tabRenderer.content.sectionListRenderer.contents.0.musicPlaylistShelfRenderer",
+    "contents.twoColumnBrowseResultsRenderer.secondaryContents.sectionListRenderer.contents.0.musicPlaylistShelfRenderer",
+    "continuationContents.musicPlaylistShelfContinuation",
+    "onResponseReceivedActions.0.appendContinuationItemsAction",
+];
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicPlaylist {
+    pub items: Vec<MusicResponsiveListItemRenderer>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicShelf {
+    #[serde(default, alias = "continuationItems")]
+    pub contents: Vec<MusicShelfItem>,
+    #[serde(default)]
+    pub continuations: Vec<Value>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicShelfItem {
+    pub music_responsive_list_item_renderer: Option<MusicResponsiveListItemRenderer>,
+    pub continuation_item_renderer: Option<Value>,
+}
+
+/// One row of a youtube music playlist, the title, artist and album columns come first and the
+/// duration sits in a fixed column
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicResponsiveListItemRenderer {
+    #[serde(default)]
+    pub flex_columns: Vec<FlexColumn>,
+    #[serde(default)]
+    pub fixed_columns: Vec<FixedColumn>,
+    pub playlist_item_data: Option<PlaylistItemData>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FlexColumn {
+    pub music_responsive_list_item_flex_column_renderer: ColumnRenderer,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixedColumn {
+    pub music_responsive_list_item_fixed_column_renderer: ColumnRenderer,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnRenderer {
+    #[serde(default)]
+    pub text: MusicText,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicText {
+    #[serde(default)]
+    pub runs: Vec<MusicRun>,
+}
+
+impl MusicText {
+    fn text(&self) -> String {
+        self.runs.iter().map(|run| run.text.as_str()).collect()
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MusicRun {
+    pub text: String,
+    pub navigation_endpoint: Option<Value>,
+}
+
+impl MusicRun {
+    /// What the run links to, MUSIC_PAGE_TYPE_ARTIST or MUSIC_PAGE_TYPE_ALBUM for the columns we read
+    fn page_type(&self) -> Option<&str> {
+        self.navigation_endpoint.as_ref()
+            .and_then(|endpoint| endpoint.pointer("/browseEndpoint/browseEndpointContextSupportedConfigs/browseEndpointContextMusicConfig/pageType"))
+            .and_then(|page_type| page_type.as_str())
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaylistItemData {
+    pub video_id: Option<String>,
+}
+
+impl MusicResponsiveListItemRenderer {
+    fn flex_text(&self, column: usize) -> Option<&MusicText> {
+        self.flex_columns.get(column).map(|column| &column.music_responsive_list_item_flex_column_renderer.text)
+    }
+
+    fn linked_runs(&self, page_type: &str) -> Vec<&MusicRun> {
+        self.flex_columns.iter()
+            .skip(1)
+            .flat_map(|column| column.music_responsive_list_item_flex_column_renderer.text.runs.iter())
+            .filter(|run| run.page_type() == Some(page_type))
+            .collect()
+    }
+
+    pub fn title(&self) -> String {
+        self.flex_text(0).map(|text| text.text()).unwrap_or_default()
+    }
+
+    /// The linked artists, or the second column split on the usual separators when nothing is linked
+    pub fn artists(&self) -> Vec<String> {
+        let linked: Vec<String> = self.linked_runs("MUSIC_PAGE_TYPE_ARTIST").iter()
+            .map(|run| run.text.trim().to_string())
+            .collect();
+        if !linked.is_empty() {
+            return linked;
+        }
+        let text = self.flex_text(1).map(|text| text.text()).unwrap_or_default();
+        text.split(" • ").next().unwrap_or_default()
+            .split([',', '&'])
+            .map(|artist| artist.trim())
+            .filter(|artist| !artist.is_empty())
+            .map(|artist| artist.to_string())
+            .collect()
+    }
+
+    pub fn album(&self) -> Option<String> {
+        self.linked_runs("MUSIC_PAGE_TYPE_ALBUM").first()
+            .map(|run| run.text.clone())
+            .or_else(|| self.flex_text(2).map(|text| text.text()))
+            .filter(|album| !album.is_empty())
+    }
+
+    /// The length in seconds, read from the fixed column
+    pub fn duration(&self) -> Option<u32> {
+        self.fixed_columns.first()
+            .map(|column| column.music_responsive_list_item_fixed_column_renderer.text.text())
+            .and_then(|text| parse_duration(&text))
+    }
+
+    pub fn video_id(&self) -> Option<String> {
+        self.playlist_item_data.as_ref().and_then(|data| data.video_id.clone())
+    }
+}
+
+/// Reads a youtube music playlist, or liked music, straight from its structured columns
+#[derive(Default, Debug, Clone)]
+pub struct YoutubeMusicProvider {
+    pub playlist: Option<String>,
+    pub cookies: Option<PathBuf>,
+    /// Saved browse responses, read instead of going to youtube music
+    pub files: Vec<PathBuf>,
+}
+
+impl YoutubeMusicProvider {
+    pub fn new(opts: &Opts) -> YoutubeMusicProvider {
+        YoutubeMusicProvider {
+            playlist: opts.youtube_playlist.clone(),
+            cookies: opts.youtube_cookies.clone(),
+            files: opts.youtube_file.clone(),
+        }
+    }
+
+    async fn gather_live(&self) -> Result<Vec<MusicResponsiveListItemRenderer>, Error> {
+        let playlist = self.playlist.as_ref().context("Either a youtube music playlist or youtube files are needed")?;
+        let cookies = match &self.cookies {
+            Some(path) => Some(read_cookie_file(path)?),
+            None => None,
+        };
+        let browse_id = browse_id(playlist);
+        if browse_id == "VLLM" && cookies.is_none() {
+            return Err(anyhow!("Liked music needs the cookies of a signed in session, pass them with --youtube_cookies"));
+        }
+        let client = build_client(cookies.as_deref())?;
+
+        println!("> Retrieving youtube music config..");
+        let html = client.get(MUSIC_URL).send().await?.text().await?;
+        let config = InnertubeConfig::from_html(&html)?;
+
+        println!("> Retrieving initial youtube music data..");
+        let body = serde_json::json!({ "context": config.context, "browseId": browse_id });
+        let value = browse(&client, &config, cookies.as_deref(), body, None).await?;
+        let (mut items, mut token) = extract_music_page(&value)?;
+        while let Some(next) = token {
+            println!("> Retrieving next page..");
+            let body = serde_json::json!({ "context": config.context, "continuation": next });
+            let value = browse(&client, &config, cookies.as_deref(), body, Some(&next)).await?;
+            let (mut page, next) = extract_music_page(&value)?;
+            items.append(&mut page);
+            token = next;
+        }
+        Ok(items)
+    }
+}
+
+#[async_trait]
+impl StreamingProvider<MusicPlaylist> for YoutubeMusicProvider {
+    async fn gather_data(&self) -> Result<MusicPlaylist, Error> {
+        let items = if self.files.is_empty() {
+            self.gather_live().await?
+        } else {
+            println!("> Reading youtube music files..");
+            get_music_from_files(&self.files)?
+        };
+
+        println!("> Importing {} tracks..", items.len());
+        Ok(MusicPlaylist { items })
+    }
+
+    fn convert_to_query(&self, item: MusicPlaylist) -> Vec<TrackDescriptor> {
+        item.items.iter().enumerate().map(|(position, item)| {
+            let artists = item.artists();
+            TrackDescriptor {
+                title: item.title(),
+                artist: artists.first().cloned().unwrap_or_default(),
+                artists,
+                album: item.album(),
+                duration: item.duration(),
+                source_id: item.video_id(),
+                position,
+                ..Default::default()
+            }
+        }).collect()
+    }
+
+    async fn build_queries(&self) -> Result<Vec<TrackDescriptor>, Error> {
+        let playlist = self.gather_data().await?;
+        let queries = self.convert_to_query(playlist);
+        Ok(queries)
+    }
+}
+
+/// Liked music lives behind the LM playlist, every playlist is browsed with a VL prefix
+fn browse_id(playlist: &str) -> String {
+    let playlist = match playlist {
+        "liked" | "likes" => "LM",
+        playlist => playlist,
+    };
+    if playlist.starts_with("VL") {
+        playlist.to_string()
+    } else {
+        format!("VL{}", playlist)
+    }
+}
+
+async fn browse(client: &Client, config: &InnertubeConfig, cookies: Option<&str>, body: Value, continuation: Option<&str>) -> Result<Value, Error> {
+    let request = client.post(&format!("{}/youtubei/v1/browse", MUSIC_URL));
+    let mut request = sign_request(request, cookies, MUSIC_URL)
+        .query(&[("key", config.api_key.as_str())])
+        .header("X-Youtube-Client-Name", "67")
+        .header("X-Origin", MUSIC_URL)
+        .header("Origin", MUSIC_URL);
+    if let Some(continuation) = continuation {
+        request = request.query(&[("ctoken", continuation), ("continuation", continuation), ("type", "next")]);
+    }
+    if let Some(version) = config.client_version() {
+        request = request.header("X-Youtube-Client-Version", version);
+    }
+    if let Some(visitor) = config.visitor_data() {
+        request = request.header("X-Goog-Visitor-Id", visitor);
+    }
+
+    let json = request.body(body.to_string()).send().await?.text().await?;
+    Ok(serde_json::from_str(json.trim_end())?)
+}
+
+/// Reads saved youtube music browse responses, first page or continuation, in the given order
+pub fn get_music_from_files(paths: &[PathBuf]) -> Result<Vec<MusicResponsiveListItemRenderer>, Error> {
+    let mut items = vec![];
+    for path in paths {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read youtube music file {}", path.display()))?;
+        let value: Value = serde_json::from_str(&contents)
+            .with_context(|| format!("Some issue with {}", path.display()))?;
+        let (mut page, _) = extract_music_page(&value)
+            .with_context(|| format!("Some issue with {}", path.display()))?;
+        items.append(&mut page);
+    }
+    Ok(items)
+}
+
+/// The tracks on a page and the token for the page after it if there is one
+fn extract_music_page(value: &Value) -> Result<(Vec<MusicResponsiveListItemRenderer>, Option<String>), Error> {
+    let mut shelf = None;
+    for path in SHELF_PATHS.iter() {
+        shelf = value.dot_get::<MusicShelf>(path)?;
+        if shelf.is_some() {
+            break;
+        }
+    }
+    let shelf = shelf.context("Failed to find the playlist shelf in the youtube music data")?;
+
+    let token = shelf.continuations.first()
+        .and_then(|continuation| continuation.pointer("/nextContinuationData/continuation"))
+        .or_else(|| shelf.contents.iter()
+            .rev()
+            .filter_map(|item| item.continuation_item_renderer.as_ref())
+            .find_map(|renderer| renderer.pointer("/continuationEndpoint/continuationCommand/token")))
+        .and_then(|token| token.as_str())
+        .map(|token| token.to_string());
+    let items = shelf.contents.into_iter()
+        .filter_map(|item| item.music_responsive_list_item_renderer)
+        .collect();
+    Ok((items, token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_fixture(path: &str) -> Value {
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn extracts_tracks_and_continuations() {
+        let (first, token) = extract_music_page(&read_fixture("ytmusic-browse.json")).unwrap();
+        assert_eq!(first.len(), 3);
+        assert_eq!(token.as_deref(), Some("4qmFsgJbEhpWTFBMdGVzdA"));
+
+        let (second, token) = extract_music_page(&read_fixture("ytmusic-continuation.json")).unwrap();
+        assert_eq!(second.len(), 2);
+        assert_eq!(token, None);
+
+        assert!(extract_music_page(&serde_json::json!({})).is_err());
+    }
+
+    #[test]
+    fn reads_the_columns() {
+        let paths = vec![PathBuf::from("ytmusic-browse.json"), PathBuf::from("ytmusic-continuation.json")];
+        let items = get_music_from_files(&paths).unwrap();
+        let strings = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<String>>();
+
+        let titles: Vec<String> = items.iter().map(|item| item.title()).collect();
+        assert_eq!(titles, strings(&["Agony", "Numb", "Lofi Mix", "Drown", "Untitled"]));
+        let artists: Vec<Vec<String>> = items.iter().map(|item| item.artists()).collect();
+        assert_eq!(artists, vec![
+            strings(&["Slaughter to Prevail"]),
+            strings(&["Linkin Park", "JAY-Z"]),
+            strings(&["Some Uploader", "Another One"]),
+            strings(&["Bring Me The Horizon"]),
+            strings(&["Unknown Artist"]),
+        ]);
+        let albums: Vec<Option<String>> = items.iter().map(|item| item.album()).collect();
+        assert_eq!(albums, vec![Some("Kostolom".to_string()), Some("Collision Course".to_string()), None, Some("That's the Spirit".to_string()), None]);
+        let durations: Vec<Option<u32>> = items.iter().map(|item| item.duration()).collect();
+        assert_eq!(durations, vec![Some(210), Some(205), Some(3723), Some(222), None]);
+        assert_eq!(items[0].video_id().as_deref(), Some("d6Xbo6FzIGs"));
+    }
+
+    #[test]
+    fn browses_playlists_with_a_vl_prefix() {
+        let cases = vec![
+            ("liked", "VLLM"),
+            ("likes", "VLLM"),
+            ("PLtest", "VLPLtest"),
+            ("VLPLtest", "VLPLtest"),
+        ];
+
+        for (playlist, expected) in cases {
+            assert_eq!(browse_id(playlist), expected, "playlist {:?}", playlist);
+        }
+    }
+}